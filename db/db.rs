@@ -1,33 +1,268 @@
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
-use std::path::Path;
-use std::fs::File;
-
-pub async fn create_pool() -> Result<SqlitePool, sqlx::Error> {
-    let db_path = "db/pocketbase_schema.db";
-    let path = Path::new(db_path);
-    
-    // Create database file if it doesn't exist
-    if !path.exists() {
-        println!("Creating new database file: {}", db_path);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
+use anyhow::{bail, Context};
+use sqlx::migrate::{Migrate, Migration, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::settings::{Database, JournalMode, Pragmas, Synchronous, SETTINGS};
+
+/// Pool used for record listings and views. Backed by the read-only replica
+/// when one is configured, by the primary database otherwise.
+#[derive(Debug, Clone)]
+pub struct ReadPool(pub SqlitePool);
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Checksums of migrations that were edited after being applied, which are
+/// replaced by the current ones instead of failing the validation. The
+/// initial schema used to include sample data, now loaded by `seed`.
+const LEGACY_CHECKSUMS: [(i64, &str); 1] = [(
+    20240901000000,
+    "174d49aa71bdf13db3a80e3b0d296033ad5ea4b113e55879359eb648fc284d64dc9fdbeedfcbd644289ea7c800795144",
+)];
+
+pub struct Pools {
+    pub primary: SqlitePool,
+    pub read: ReadPool,
+}
+
+/// Opens the configured database and applies the pending migrations.
+pub async fn create_pool() -> anyhow::Result<Pools> {
+    let pools = connect(&SETTINGS.database).await?;
+    migrate(&pools.primary, false).await?;
+
+    Ok(pools)
+}
+
+/// Runs the pending migrations. Destructive ones are refused on a non-empty
+/// production database unless `allow_destructive` is set.
+pub async fn migrate(pool: &SqlitePool, allow_destructive: bool) -> anyhow::Result<()> {
+    repair_checksums(pool).await?;
+
+    let applied = applied_versions(pool).await?;
+    let pending: Vec<&Migration> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect();
+    guard_destructive(pool, &pending, allow_destructive).await?;
+
+    info!("Running database migrations...");
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("Failed to run database migrations")?;
+    info!("Database setup complete");
+
+    Ok(())
+}
+
+/// Versions of the applied migrations, in ascending order.
+pub async fn applied_versions(pool: &SqlitePool) -> anyhow::Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+
+    Ok(versions)
+}
+
+/// Fails if `migrations` would drop or delete data from a non-empty
+/// production database and `allow_destructive` is not set.
+pub async fn guard_destructive(
+    pool: &SqlitePool,
+    migrations: &[&Migration],
+    allow_destructive: bool,
+) -> anyhow::Result<()> {
+    if allow_destructive || !SETTINGS.is_production() {
+        return Ok(());
+    }
+
+    let destructive: Vec<String> = migrations
+        .iter()
+        .filter(|migration| is_destructive(migration))
+        .map(|migration| format!("{}/{}", migration.version, migration.description))
+        .collect();
+    if destructive.is_empty() || !has_data(pool).await? {
+        return Ok(());
+    }
+
+    bail!(
+        "Refusing to run destructive migrations on a non-empty production database: {}. Pass --allow-destructive to run them anyway.",
+        destructive.join(", ")
+    );
+}
+
+fn is_destructive(migration: &Migration) -> bool {
+    let sql = migration.sql.to_uppercase();
+
+    migration.migration_type.is_down_migration()
+        || ["DROP TABLE", "DROP COLUMN", "DELETE FROM"]
+            .iter()
+            .any(|statement| sql.contains(statement))
+}
+
+async fn has_data(pool: &SqlitePool) -> anyhow::Result<bool> {
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != '_sqlx_migrations'",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for table in tables {
+        let exists: bool = sqlx::query_scalar(&format!("SELECT EXISTS(SELECT 1 FROM \"{table}\")"))
+            .fetch_one(pool)
+            .await?;
+        if exists {
+            return Ok(true);
         }
-        File::create(db_path).unwrap();
-    } else {
-        println!("Using existing database: {}", db_path);
     }
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&format!("sqlite:{}", db_path))
+    Ok(false)
+}
+
+async fn repair_checksums(pool: &SqlitePool) -> anyhow::Result<()> {
+    pool.acquire().await?.ensure_migrations_table().await?;
+
+    for (version, legacy) in LEGACY_CHECKSUMS {
+        let Some(migration) = MIGRATOR
+            .iter()
+            .find(|migration| migration.version == version && !migration.migration_type.is_down_migration())
+        else {
+            continue;
+        };
+        let legacy: Vec<u8> = (0..legacy.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&legacy[i..i + 2], 16))
+            .collect::<Result<_, _>>()?;
+
+        let result = sqlx::query("UPDATE _sqlx_migrations SET checksum = ? WHERE version = ? AND checksum = ?")
+            .bind(migration.checksum.as_ref())
+            .bind(version)
+            .bind(legacy)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() > 0 {
+            warn!(version, description = %migration.description, "Updated the checksum of an edited migration");
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the database described by `settings`, without migrating it.
+pub async fn connect(settings: &Database) -> anyhow::Result<Pools> {
+    let options = SqliteConnectOptions::from_str(&settings.uri)
+        .with_context(|| format!("Invalid database uri {:?}", settings.uri))?
+        .create_if_missing(true);
+    let db_path = options.clone().get_filename().into_owned();
+    // In-memory databases are given a `file:` URI name and have no file to
+    // create
+    let in_memory = db_path.to_string_lossy().starts_with("file:");
+
+    if !in_memory {
+        if db_path.exists() {
+            info!("Using existing database: {}", db_path.display());
+        } else {
+            info!("Creating new database file: {}", db_path.display());
+            if let Some(parent) = db_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create database directory {}", parent.display()))?;
+            }
+        }
+    }
+
+    let mut primary_pool = pool_options(settings);
+    if in_memory {
+        // An in-memory database is dropped with its last connection
+        primary_pool = primary_pool
+            .min_connections(settings.min_connections.max(1))
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+    let options = primary_options(connect_options(options, &settings.pragmas), &settings.pragmas);
+    let primary = primary_pool
+        .connect_with(options)
+        .await
+        .with_context(|| format!("Failed to open database {}", settings.uri))?;
+
+    check_foreign_keys(&primary).await?;
+
+    let read = match &settings.replica {
+        Some(replica) => {
+            info!("Using read-only replica: {}", replica);
+            let options = SqliteConnectOptions::new().filename(replica).read_only(true);
+            let pool = pool_options(settings)
+                .connect_with(connect_options(options, &settings.pragmas))
+                .await
+                .with_context(|| format!("Failed to open database replica {}", replica))?;
+            ReadPool(pool)
+        }
+        None => ReadPool(primary.clone()),
+    };
+
+    Ok(Pools { primary, read })
+}
+
+fn pool_options(settings: &Database) -> SqlitePoolOptions {
+    SqlitePoolOptions::new()
+        .max_connections(settings.max_connections)
+        .min_connections(settings.min_connections)
+        .acquire_timeout(Duration::from_millis(settings.acquire_timeout_ms))
+}
+
+/// Pragmas applied to every connection, including the read-only replica ones.
+fn connect_options(options: SqliteConnectOptions, pragmas: &Pragmas) -> SqliteConnectOptions {
+    options
+        .foreign_keys(true)
+        .busy_timeout(Duration::from_millis(pragmas.busy_timeout_ms))
+        .pragma("cache_size", pragmas.cache_size.to_string())
+}
+
+/// Pragmas that write to the database file and so only apply to the primary.
+fn primary_options(options: SqliteConnectOptions, pragmas: &Pragmas) -> SqliteConnectOptions {
+    let journal_mode = match pragmas.journal_mode {
+        JournalMode::Delete => SqliteJournalMode::Delete,
+        JournalMode::Truncate => SqliteJournalMode::Truncate,
+        JournalMode::Persist => SqliteJournalMode::Persist,
+        JournalMode::Memory => SqliteJournalMode::Memory,
+        JournalMode::Wal => SqliteJournalMode::Wal,
+        JournalMode::Off => SqliteJournalMode::Off,
+    };
+    let synchronous = match pragmas.synchronous {
+        Synchronous::Off => SqliteSynchronous::Off,
+        Synchronous::Normal => SqliteSynchronous::Normal,
+        Synchronous::Full => SqliteSynchronous::Full,
+        Synchronous::Extra => SqliteSynchronous::Extra,
+    };
+
+    options.journal_mode(journal_mode).synchronous(synchronous)
+}
+
+/// The `ON DELETE` actions of the schema are silently ignored when foreign
+/// keys are off, e.g. with an SQLite build compiled without them, so refuse to
+/// start in that case.
+async fn check_foreign_keys(pool: &SqlitePool) -> anyhow::Result<()> {
+    let enabled: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(pool)
         .await?;
+    if !enabled {
+        anyhow::bail!("SQLite foreign key enforcement could not be enabled");
+    }
 
-    // Run migrations
-    println!("Running database migrations...");
-    sqlx::migrate!("./migrations")
-        .run(&pool)
+    let violations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_foreign_key_check")
+        .fetch_one(pool)
         .await?;
-    println!("Database setup complete");
+    if violations > 0 {
+        warn!(violations, "The database contains foreign key violations, run `PRAGMA foreign_key_check` for details");
+    }
 
-    Ok(pool)
-}
\ No newline at end of file
+    Ok(())
+}
@@ -1,9 +1,5 @@
 -- Create all VieShare tables based on PocketBase schema design
 
--- Drop existing simple tables
-DROP TABLE IF EXISTS cats;
-DROP TABLE IF EXISTS users;
-
 -- Users table (PocketBase compatible)
 CREATE TABLE IF NOT EXISTS users (
     id TEXT PRIMARY KEY,
@@ -275,38 +271,3 @@ AFTER UPDATE ON notifications FOR EACH ROW
 BEGIN 
     UPDATE notifications SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
 END;
-
--- Insert sample data
-INSERT INTO categories (id, name, slug, description) VALUES 
-('cat_vieboards', 'Vieboards', 'vieboards', 'The best vieboards for all levels of viers.'),
-('cat_clothing', 'Clothing', 'clothing', 'Skateboarding apparel and accessories.'),
-('cat_shoes', 'Shoes', 'shoes', 'Skateboarding shoes and footwear.'),
-('cat_accessories', 'Accessories', 'accessories', 'Skateboarding accessories and gear.');
-
-INSERT INTO subcategories (id, name, slug, description, category) VALUES 
-('subcat_decks', 'Decks', 'decks', 'Skateboard decks', 'cat_vieboards'),
-('subcat_wheels', 'Wheels', 'wheels', 'Skateboard wheels', 'cat_vieboards'),
-('subcat_bearings', 'Bearings', 'bearings', 'Skateboard bearings', 'cat_vieboards'),
-('subcat_trucks', 'Trucks', 'trucks', 'Skateboard trucks', 'cat_vieboards'),
-('subcat_tshirts', 'T-shirts', 't-shirts', 'Skateboarding t-shirts', 'cat_clothing'),
-('subcat_hoodies', 'Hoodies', 'hoodies', 'Skateboarding hoodies', 'cat_clothing'),
-('subcat_sneakers', 'Sneakers', 'sneakers', 'Skateboarding sneakers', 'cat_shoes'),
-('subcat_bags', 'Bags', 'bags', 'Skateboarding bags', 'cat_accessories'),
-('subcat_helmets', 'Helmets', 'helmets', 'Skateboarding helmets', 'cat_accessories');
-
--- Sample user
-INSERT INTO users (id, email, username, name, verified) VALUES 
-('user_sample_123', 'admin@vieshare.com', 'admin', 'VieShare Admin', TRUE);
-
--- Sample store
-INSERT INTO stores (id, name, slug, description, user) VALUES 
-('store_sample_123', 'VieShare Store', 'vieshare-store', 'Official VieShare skateboarding store', 'user_sample_123');
-
--- Sample products
-INSERT INTO products (id, name, description, images, category, subcategory, price, inventory, rating, store, active) VALUES 
-('prod_deck_001', 'Street Vieboard Deck', 'High-quality maple deck perfect for street skating', 
- '["deck-1.webp", "deck-2.webp"]', 'cat_vieboards', 'subcat_decks', '59.99', 25, 4.5, 'store_sample_123', TRUE),
-('prod_wheels_001', 'Pro Skateboard Wheels', 'Premium urethane wheels for smooth rides', 
- '["wheels-1.webp", "wheels-2.webp"]', 'cat_vieboards', 'subcat_wheels', '29.99', 50, 4.2, 'store_sample_123', TRUE),
-('prod_tshirt_001', 'VieShare Logo T-Shirt', 'Comfortable cotton t-shirt with VieShare logo', 
- '["tshirt-1.webp", "tshirt-2.webp"]', 'cat_clothing', 'subcat_tshirts', '19.99', 100, 4.0, 'store_sample_123', TRUE);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Password hash used by auth-with-password. Empty for accounts without a
+-- password.
+ALTER TABLE users ADD COLUMN password_hash TEXT NOT NULL DEFAULT '';
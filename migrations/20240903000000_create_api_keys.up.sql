@@ -0,0 +1,17 @@
+-- Long-lived credentials for server-to-server integrations, scoped to a store.
+-- Only a SHA-256 hash of the key is stored, the prefix identifies the key.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    store TEXT NOT NULL,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL UNIQUE,
+    key_hash TEXT NOT NULL,
+    scopes TEXT NOT NULL, -- JSON array as text
+    last_used_at TEXT,
+    revoked_at TEXT,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (store) REFERENCES stores (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_store ON api_keys (store);
@@ -0,0 +1,67 @@
+-- The normalized timestamps are kept, they are read like the old ones.
+
+CREATE TRIGGER update_users_updated_at 
+AFTER UPDATE ON users FOR EACH ROW 
+BEGIN 
+    UPDATE users SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_categories_updated_at 
+AFTER UPDATE ON categories FOR EACH ROW 
+BEGIN 
+    UPDATE categories SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_subcategories_updated_at 
+AFTER UPDATE ON subcategories FOR EACH ROW 
+BEGIN 
+    UPDATE subcategories SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_stores_updated_at 
+AFTER UPDATE ON stores FOR EACH ROW 
+BEGIN 
+    UPDATE stores SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_products_updated_at 
+AFTER UPDATE ON products FOR EACH ROW 
+BEGIN 
+    UPDATE products SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_carts_updated_at 
+AFTER UPDATE ON carts FOR EACH ROW 
+BEGIN 
+    UPDATE carts SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_cart_items_updated_at 
+AFTER UPDATE ON cart_items FOR EACH ROW 
+BEGIN 
+    UPDATE cart_items SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_addresses_updated_at 
+AFTER UPDATE ON addresses FOR EACH ROW 
+BEGIN 
+    UPDATE addresses SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_orders_updated_at 
+AFTER UPDATE ON orders FOR EACH ROW 
+BEGIN 
+    UPDATE orders SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_customers_updated_at 
+AFTER UPDATE ON customers FOR EACH ROW 
+BEGIN 
+    UPDATE customers SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
+
+CREATE TRIGGER update_notifications_updated_at 
+AFTER UPDATE ON notifications FOR EACH ROW 
+BEGIN 
+    UPDATE notifications SET updated = CURRENT_TIMESTAMP WHERE id = NEW.id; 
+END;
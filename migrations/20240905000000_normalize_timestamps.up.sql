@@ -0,0 +1,60 @@
+-- Timestamps are written by the application as UTC with milliseconds
+-- (`2024-09-05 10:00:00.000Z`). The triggers overwrote `updated` with
+-- `CURRENT_TIMESTAMP`, which has neither, so remove them and rewrite the
+-- values stored in the old format.
+
+DROP TRIGGER IF EXISTS update_users_updated_at;
+DROP TRIGGER IF EXISTS update_categories_updated_at;
+DROP TRIGGER IF EXISTS update_subcategories_updated_at;
+DROP TRIGGER IF EXISTS update_stores_updated_at;
+DROP TRIGGER IF EXISTS update_products_updated_at;
+DROP TRIGGER IF EXISTS update_carts_updated_at;
+DROP TRIGGER IF EXISTS update_cart_items_updated_at;
+DROP TRIGGER IF EXISTS update_addresses_updated_at;
+DROP TRIGGER IF EXISTS update_orders_updated_at;
+DROP TRIGGER IF EXISTS update_customers_updated_at;
+DROP TRIGGER IF EXISTS update_notifications_updated_at;
+
+UPDATE users SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE categories SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE subcategories SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE stores SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE products SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE carts SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE cart_items SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE addresses SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE orders SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE customers SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE notifications SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE api_keys SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE _superusers SET
+    created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created),
+    updated = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', updated), updated);
+UPDATE audit_logs SET created = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', created), created);
+UPDATE api_keys SET
+    revoked_at = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', revoked_at), revoked_at),
+    last_used_at = coalesce(strftime('%Y-%m-%d %H:%M:%fZ', last_used_at), last_used_at);
@@ -0,0 +1,2 @@
+ALTER TABLE orders DROP COLUMN downloads;
+ALTER TABLE orders DROP COLUMN invoice;
@@ -0,0 +1,3 @@
+-- Protected files of an order, only served to those who may view it.
+ALTER TABLE orders ADD COLUMN invoice TEXT;
+ALTER TABLE orders ADD COLUMN downloads TEXT; -- JSON array as text
@@ -0,0 +1,4 @@
+-- Orders placed together from a cart holding products of several stores,
+-- one order per store.
+ALTER TABLE orders ADD COLUMN checkout TEXT;
+CREATE INDEX IF NOT EXISTS idx_orders_checkout ON orders (checkout);
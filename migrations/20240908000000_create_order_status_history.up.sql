@@ -0,0 +1,13 @@
+-- Status changes of orders, with the user or superuser who made them
+CREATE TABLE IF NOT EXISTS order_status_history (
+    id TEXT PRIMARY KEY,
+    "order" TEXT NOT NULL,
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    note TEXT,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("order") REFERENCES orders (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_status_history_order ON order_status_history ("order");
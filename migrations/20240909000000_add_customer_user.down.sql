@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_customers_store_user;
+ALTER TABLE customers DROP COLUMN user;
@@ -0,0 +1,13 @@
+-- Customers of checkout are the buyer's account rather than the email
+-- entered at checkout, which anyone could set to someone else's.
+ALTER TABLE customers ADD COLUMN user TEXT REFERENCES users (id) ON DELETE SET NULL;
+-- Existing customers were created from the email of their orders, so they
+-- belong to the account that placed those orders. Customers whose email was
+-- used by several accounts in the store are left without one.
+UPDATE customers SET user = (
+    SELECT MIN(orders.user) FROM orders
+    WHERE orders.store = customers.store AND orders.email = customers.email AND orders.user IS NOT NULL
+    GROUP BY orders.store
+    HAVING COUNT(DISTINCT orders.user) = 1
+);
+CREATE INDEX IF NOT EXISTS idx_customers_store_user ON customers (store, user);
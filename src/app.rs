@@ -1,5 +1,5 @@
 use axum::http::header;
-use axum::Router;
+use axum::{middleware, Router};
 use sqlx::SqlitePool;
 use tower_http::{
     compression::CompressionLayer, cors::CorsLayer, propagate_header::PropagateHeaderLayer,
@@ -7,14 +7,25 @@ use tower_http::{
 };
 
 
+use crate::db::ReadPool;
+use crate::models::realtime::Realtime;
 use crate::routes;
+use crate::utils::auth::trace_impersonation;
 
-pub async fn create_app(pool: SqlitePool) -> Router {
-    
+pub async fn create_app(pool: SqlitePool, read_pool: ReadPool) -> Router {
+    let realtime = Realtime::new(pool.clone());
 
     Router::new()
         .merge(routes::status::create_route())
-        .merge(routes::pocketbase::create_route(pool))
+        .merge(routes::pocketbase::create_route(pool.clone(), read_pool.clone(), realtime.clone()))
+        .merge(routes::files::create_route(pool.clone(), read_pool))
+        .merge(routes::batch::create_route(pool.clone(), realtime.clone()))
+        .merge(routes::carts::create_route(pool.clone(), realtime.clone()))
+        .merge(routes::orders::create_route(pool.clone(), realtime.clone()))
+        .merge(routes::realtime::create_route(pool.clone(), realtime))
+        .merge(routes::api_keys::create_route(pool.clone()))
+        // Tag requests made by superusers impersonating a user
+        .layer(middleware::from_fn_with_state(pool, trace_impersonation))
         // High level logging of requests and responses
         .layer(
             trace::TraceLayer::new_for_http()
@@ -0,0 +1,41 @@
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::models::storage::Storage;
+
+/// Key prefix of the backups in the storage, apart from the record files
+/// kept under `<collection>/<record id>/`.
+pub const BACKUP_PREFIX: &str = "backups/";
+
+/// Writes a consistent snapshot of the database to `storage` and returns
+/// its key. The snapshot is taken with `VACUUM INTO`, so the server can keep
+/// running meanwhile.
+pub async fn run(pool: &SqlitePool, storage: &dyn Storage) -> anyhow::Result<String> {
+    let snapshot = std::env::temp_dir().join(format!("vieshare-backup-{}.db", Uuid::new_v4()));
+    let result = upload(pool, storage, &snapshot.to_string_lossy()).await;
+    let _ = tokio::fs::remove_file(&snapshot).await;
+
+    let key = result?;
+    println!("Backup written to {key}");
+    Ok(key)
+}
+
+async fn upload(pool: &SqlitePool, storage: &dyn Storage, snapshot: &str) -> anyhow::Result<String> {
+    sqlx::query("VACUUM INTO ?")
+        .bind(snapshot)
+        .execute(pool)
+        .await
+        .context("Failed to snapshot the database")?;
+    let data = tokio::fs::read(snapshot).await.context("Failed to read the snapshot")?;
+
+    let key = format!("{BACKUP_PREFIX}{}.db", Utc::now().format("%Y%m%d%H%M%S"));
+    storage
+        .write(&key, Bytes::from(data))
+        .await
+        .with_context(|| format!("Failed to store the backup as {key}"))?;
+
+    Ok(key)
+}
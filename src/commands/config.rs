@@ -0,0 +1,41 @@
+use anyhow::Context;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::models::password::{PasswordPolicy, Passwords};
+use crate::models::storage;
+use crate::settings::{Settings, StorageDriver};
+
+/// Loads the configuration the same way the server does and checks the
+/// values that are otherwise only validated on first use.
+pub fn check() -> anyhow::Result<()> {
+    let settings = Settings::new().context("Failed to load settings")?;
+
+    settings
+        .logger
+        .level
+        .parse::<tracing::Level>()
+        .with_context(|| format!("Invalid logger.level {:?}", settings.logger.level))?;
+    SqliteConnectOptions::from_str(&settings.database.uri)
+        .with_context(|| format!("Invalid database.uri {:?}", settings.database.uri))?;
+    if let Some(replica) = &settings.database.replica {
+        if !Path::new(replica).exists() {
+            anyhow::bail!("database.replica {replica:?} does not exist");
+        }
+    }
+    Passwords::from_settings(&settings.auth.password).context("Invalid auth.password settings")?;
+    storage::from_settings(&settings.storage).context("Invalid storage settings")?;
+    PasswordPolicy::from_settings(&settings.auth.password).context("Failed to load auth.password.breached_list")?;
+
+    println!("Configuration is valid");
+    println!("  environment: {}", settings.environment);
+    println!("  server:      {}", settings.server);
+    println!("  database:    {}", settings.database.uri);
+    match settings.storage.driver {
+        StorageDriver::Local => println!("  storage:     {}", settings.storage.dir),
+        StorageDriver::S3 => println!("  storage:     s3 {} ({})", settings.storage.s3.bucket, settings.storage.s3.endpoint),
+    }
+
+    Ok(())
+}
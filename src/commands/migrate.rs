@@ -0,0 +1,62 @@
+use anyhow::{bail, Context};
+use sqlx::SqlitePool;
+
+use crate::commands::MigrateCommand;
+use crate::db::{self, applied_versions, MIGRATOR};
+
+pub async fn run(pool: &SqlitePool, action: MigrateCommand) -> anyhow::Result<()> {
+    match action {
+        MigrateCommand::Up { allow_destructive } => db::migrate(pool, allow_destructive).await,
+        MigrateCommand::Down { allow_destructive } => down(pool, allow_destructive).await,
+        MigrateCommand::Status => status(pool).await,
+    }
+}
+
+async fn down(pool: &SqlitePool, allow_destructive: bool) -> anyhow::Result<()> {
+    let applied = applied_versions(pool).await?;
+    let Some((&last, rest)) = applied.split_last() else {
+        println!("No migrations to revert");
+        return Ok(());
+    };
+
+    let Some(migration) = MIGRATOR
+        .iter()
+        .find(|migration| migration.version == last && migration.migration_type.is_down_migration())
+    else {
+        bail!("Migration {last} is not reversible");
+    };
+    db::guard_destructive(pool, &[migration], allow_destructive).await?;
+
+    MIGRATOR
+        .undo(pool, rest.last().copied().unwrap_or(0))
+        .await
+        .with_context(|| format!("Failed to revert migration {last}"))?;
+    println!("Reverted {}/{}", migration.version, migration.description);
+
+    Ok(())
+}
+
+async fn status(pool: &SqlitePool) -> anyhow::Result<()> {
+    let applied = applied_versions(pool).await?;
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        let state = if applied.contains(&migration.version) {
+            "applied"
+        } else {
+            "pending"
+        };
+        println!("{:<8} {}/{}", state, migration.version, migration.description);
+    }
+
+    for version in applied
+        .iter()
+        .filter(|version| !MIGRATOR.iter().any(|migration| migration.version == **version))
+    {
+        println!("{:<8} {}/<missing from ./migrations>", "unknown", version);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+pub mod backup;
+pub mod config;
+pub mod migrate;
+pub mod seed;
+pub mod superuser;
+
+/// VieShare API server and management commands.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (default)
+    Serve {
+        /// Load the sample data first, leaving existing records untouched
+        #[arg(long)]
+        seed: bool,
+    },
+    /// Manage the database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateCommand,
+    },
+    /// Import JSON/CSV datasets, updating the records that already exist
+    Seed {
+        /// `.json` documents, `<collection>.csv` files or directories of them
+        #[arg(default_value = seed::SAMPLE_DATA)]
+        paths: Vec<PathBuf>,
+        /// Leave existing records untouched instead of updating them
+        #[arg(long)]
+        skip_existing: bool,
+        /// Seed even if the environment is production
+        #[arg(long)]
+        force: bool,
+    },
+    /// Manage superuser accounts
+    Superuser {
+        #[command(subcommand)]
+        action: SuperuserCommand,
+    },
+    /// Write a snapshot of the database to the configured storage
+    Backup,
+    /// Validate the configuration and exit
+    CheckConfig,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply all pending migrations
+    Up {
+        /// Run migrations that drop data on a non-empty production database
+        #[arg(long)]
+        allow_destructive: bool,
+    },
+    /// Revert the last applied migration
+    Down {
+        /// Revert even on a non-empty production database
+        #[arg(long)]
+        allow_destructive: bool,
+    },
+    /// List the migrations and whether they are applied
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SuperuserCommand {
+    /// Create a new superuser. The password is read from `SUPERUSER_PASSWORD`,
+    /// prompted for on a terminal or read from stdin.
+    Create { email: String },
+    /// Change the password of an existing superuser, read like for `create`
+    Update { email: String },
+    /// Delete a superuser
+    Delete { email: String },
+}
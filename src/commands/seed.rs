@@ -0,0 +1,32 @@
+use anyhow::bail;
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+
+use crate::models::fixture::{self, Dataset, OnConflict};
+
+pub const SAMPLE_DATA: &str = "data/sample.json";
+
+/// Imports the given JSON/CSV datasets and prints what was imported. Fails
+/// if any record was rejected; the valid ones are still imported.
+pub async fn run(pool: &SqlitePool, paths: &[PathBuf], on_conflict: OnConflict) -> anyhow::Result<()> {
+    let mut dataset = Dataset::default();
+    for path in paths {
+        dataset.add(pool, path).await?;
+    }
+
+    let report = fixture::import(pool, &dataset, on_conflict).await?;
+    for (collection, imported) in &report.imported {
+        println!("{collection}: {imported} imported");
+    }
+    if report.skipped > 0 {
+        println!("{} existing record(s) skipped", report.skipped);
+    }
+    for error in &report.errors {
+        eprintln!("{error}");
+    }
+    if !report.errors.is_empty() {
+        bail!("{} record(s) could not be imported", report.errors.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::{bail, Context};
+use sqlx::SqlitePool;
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::commands::SuperuserCommand;
+use crate::models::password::PASSWORD_POLICY;
+use crate::models::pocketbase::{current_timestamp, generate_id};
+use crate::models::user::hash_password;
+
+pub async fn run(pool: &SqlitePool, action: SuperuserCommand) -> anyhow::Result<()> {
+    match action {
+        SuperuserCommand::Create { email } => create(pool, &email, read_password()?).await,
+        SuperuserCommand::Update { email } => update(pool, &email, read_password()?).await,
+        SuperuserCommand::Delete { email } => delete(pool, &email).await,
+    }
+}
+
+/// Environment variable holding the password, for scripted setups.
+const PASSWORD_ENV: &str = "SUPERUSER_PASSWORD";
+
+/// Reads the password without it appearing in the process arguments: from
+/// `SUPERUSER_PASSWORD`, an interactive prompt asking for it twice, or the
+/// first line of stdin when it is not a terminal.
+fn read_password() -> anyhow::Result<String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+
+    if io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ").context("Failed to read the password")?;
+        let confirmation = rpassword::prompt_password("Confirm password: ").context("Failed to read the password")?;
+        if password != confirmation {
+            bail!("Passwords don't match");
+        }
+        return Ok(password);
+    }
+
+    let mut password = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password from stdin")?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn hash(password: String) -> anyhow::Result<String> {
+    if let Err(err) = PASSWORD_POLICY.check(&password) {
+        bail!("Invalid password: {err}");
+    }
+
+    Ok(hash_password(password).await?)
+}
+
+async fn create(pool: &SqlitePool, email: &str, password: String) -> anyhow::Result<()> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM _superusers WHERE email = ?)")
+        .bind(email)
+        .fetch_one(pool)
+        .await?;
+    if exists {
+        bail!("Superuser {email} already exists");
+    }
+
+    let password_hash = hash(password).await?;
+    let now = current_timestamp();
+    sqlx::query(
+        "INSERT INTO _superusers (id, email, password_hash, created, updated) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(generate_id())
+    .bind(email)
+    .bind(password_hash)
+    .bind(&now)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+    println!("Superuser {email} created");
+
+    Ok(())
+}
+
+async fn update(pool: &SqlitePool, email: &str, password: String) -> anyhow::Result<()> {
+    let password_hash = hash(password).await?;
+    let result = sqlx::query("UPDATE _superusers SET password_hash = ?, updated = ? WHERE email = ?")
+        .bind(password_hash)
+        .bind(current_timestamp())
+        .bind(email)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        bail!("Superuser {email} not found");
+    }
+    println!("Superuser {email} updated");
+
+    Ok(())
+}
+
+async fn delete(pool: &SqlitePool, email: &str) -> anyhow::Result<()> {
+    let result = sqlx::query("DELETE FROM _superusers WHERE email = ?")
+        .bind(email)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        bail!("Superuser {email} not found");
+    }
+    println!("Superuser {email} deleted");
+
+    Ok(())
+}
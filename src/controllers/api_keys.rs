@@ -0,0 +1,75 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use axum_extra::extract::WithRejection;
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::errors::Error;
+use crate::forms::api_key::CreateApiKey;
+use crate::models::api_key::ApiKey;
+use crate::utils::auth::Auth;
+
+/// API keys are managed by the store owner, with a user token. Superusers
+/// impersonating the owner may not create long-lived credentials.
+async fn authorize_store_owner(pool: &SqlitePool, auth: &Auth, store: &str) -> Result<(), Error> {
+    let user = auth
+        .user()
+        .filter(|user| user.impersonator.is_none())
+        .ok_or_else(Error::forbidden)?;
+    let owner: Option<String> = sqlx::query_scalar("SELECT user FROM stores WHERE id = ?")
+        .bind(store)
+        .fetch_optional(pool)
+        .await?;
+
+    match owner {
+        Some(owner) if owner == user.id => Ok(()),
+        Some(_) => Err(Error::forbidden()),
+        None => Err(Error::not_found()),
+    }
+}
+
+pub async fn list(
+    State(pool): State<SqlitePool>,
+    auth: Auth,
+    Path(store): Path<String>,
+) -> Result<Json<Value>, Error> {
+    authorize_store_owner(&pool, &auth, &store).await?;
+    let keys = ApiKey::list_for_store(&pool, &store).await?;
+    Ok(Json(json!({ "items": keys })))
+}
+
+pub async fn create(
+    State(pool): State<SqlitePool>,
+    auth: Auth,
+    Path(store): Path<String>,
+    WithRejection(Json(form), _): WithRejection<Json<CreateApiKey>, Error>,
+) -> Result<(StatusCode, Json<Value>), Error> {
+    authorize_store_owner(&pool, &auth, &store).await?;
+    form.validate()?;
+
+    let (api_key, key) = ApiKey::generate(store, form.name, form.scopes);
+    api_key.insert(&pool).await?;
+
+    // The plain text key is only ever returned in this response.
+    let mut body = json!(api_key);
+    body["key"] = json!(key);
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+pub async fn revoke(
+    State(pool): State<SqlitePool>,
+    auth: Auth,
+    Path((store, id)): Path<(String, String)>,
+) -> Result<StatusCode, Error> {
+    authorize_store_owner(&pool, &auth, &store).await?;
+
+    if ApiKey::revoke(&pool, &store, &id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(Error::not_found())
+    }
+}
@@ -0,0 +1,200 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::Json,
+};
+use axum_extra::extract::WithRejection;
+use serde_json::{json, Map, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+use validator::Validate;
+
+use crate::errors::{BadRequest, Error};
+use crate::forms::batch::{Batch, BatchRequest};
+use crate::models::pocketbase::{Address, Cart, CartItem, Category, Customer, Order, Product, Store, User};
+use crate::models::realtime::{EventAction, Realtime};
+use crate::models::record::{self, PendingFiles, Record};
+use crate::utils::auth::{Auth, OptionalAuth};
+
+/// What a batch request does to its collection.
+enum Operation {
+    Create,
+    /// Updates the record with the `id` of the body, or creates it.
+    Upsert,
+    Update(String),
+    Delete(String),
+}
+
+impl Operation {
+    /// Reads the collection and operation of a request from its method and
+    /// url, as the equivalent record API call would.
+    fn parse(request: &BatchRequest) -> Result<(String, Self), Error> {
+        let path = request.url.split(['?', '#']).next().unwrap_or_default();
+        let segments: Vec<&str> = path
+            .strip_prefix("/api/collections/")
+            .map(|path| path.split('/').collect())
+            .unwrap_or_default();
+
+        let operation = match (request.method.to_ascii_uppercase().as_str(), segments.as_slice()) {
+            ("POST", [collection, "records"]) => (collection, Self::Create),
+            ("PUT", [collection, "records"]) => (collection, Self::Upsert),
+            ("PATCH", [collection, "records", id]) => (collection, Self::Update(id.to_string())),
+            ("DELETE", [collection, "records", id]) => (collection, Self::Delete(id.to_string())),
+            _ => {
+                let message = format!("Unsupported batch request {} {}.", request.method, request.url);
+                return Err(BadRequest::new(message).into());
+            }
+        };
+
+        Ok((operation.0.to_string(), operation.1))
+    }
+}
+
+/// Result of a batch request, and the realtime event published once the
+/// batch is committed.
+struct Outcome {
+    status: StatusCode,
+    body: Value,
+    action: EventAction,
+    record: Value,
+}
+
+/// Runs record requests in a single transaction. Each request is checked
+/// against the collection rules with the credentials of the batch, and the
+/// whole batch is rolled back if any of them fails.
+pub async fn execute(
+    State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    OptionalAuth(auth): OptionalAuth,
+    WithRejection(Json(batch), _): WithRejection<Json<Batch>, Error>,
+) -> Result<Json<Value>, Error> {
+    batch.validate()?;
+
+    let mut tx = pool.begin().await?;
+    let mut files = PendingFiles::default();
+    let mut outcomes = Vec::with_capacity(batch.requests.len());
+    for (index, request) in batch.requests.into_iter().enumerate() {
+        match run(&mut tx, auth.as_ref(), request, &mut files).await {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(err) => {
+                drop(tx);
+                files.rolled_back().await;
+                return Err(request_failed(index, err));
+            }
+        }
+    }
+    if let Err(err) = tx.commit().await {
+        files.rolled_back().await;
+        return Err(err.into());
+    }
+    files.committed().await;
+
+    let mut responses = Vec::with_capacity(outcomes.len());
+    for (collection, outcome) in outcomes {
+        realtime.publish(&collection, outcome.action, outcome.record);
+        responses.push(json!({ "status": outcome.status.as_u16(), "body": outcome.body }));
+    }
+
+    Ok(Json(Value::Array(responses)))
+}
+
+async fn run(
+    tx: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    request: BatchRequest,
+    files: &mut PendingFiles,
+) -> Result<(String, Outcome), Error> {
+    let (collection, operation) = Operation::parse(&request)?;
+    let body = request.body;
+    let outcome = match collection.as_str() {
+        // Users are registered with a password, which only the sign up
+        // endpoint accepts
+        "users" if matches!(operation, Operation::Create | Operation::Upsert) => {
+            return Err(BadRequest::new("Users cannot be created in a batch.").into());
+        }
+        "users" => run_operation::<User>(tx, auth, operation, body, files).await,
+        "categories" => run_operation::<Category>(tx, auth, operation, body, files).await,
+        "stores" => run_operation::<Store>(tx, auth, operation, body, files).await,
+        "products" => run_operation::<Product>(tx, auth, operation, body, files).await,
+        "orders" => run_operation::<Order>(tx, auth, operation, body, files).await,
+        "customers" => run_operation::<Customer>(tx, auth, operation, body, files).await,
+        "carts" => run_operation::<Cart>(tx, auth, operation, body, files).await,
+        "cart_items" => run_operation::<CartItem>(tx, auth, operation, body, files).await,
+        "addresses" => run_operation::<Address>(tx, auth, operation, body, files).await,
+        _ => Err(Error::not_found()),
+    }?;
+
+    Ok((collection, outcome))
+}
+
+async fn run_operation<T: Record>(
+    tx: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    operation: Operation,
+    body: Value,
+    files: &mut PendingFiles,
+) -> Result<Outcome, Error> {
+    let operation = match operation {
+        Operation::Upsert => {
+            let id = body.get("id").and_then(Value::as_str).filter(|id| !id.is_empty());
+            match id {
+                Some(id) if record::find::<T>(tx, id).await?.is_some() => Operation::Update(id.to_string()),
+                _ => Operation::Create,
+            }
+        }
+        operation => operation,
+    };
+
+    let (action, record) = match operation {
+        Operation::Create | Operation::Upsert => {
+            let record = record::create_in::<T>(tx, auth, body.into(), files).await?;
+            (EventAction::Create, json!(record))
+        }
+        Operation::Update(id) => {
+            let record = record::update_in::<T>(tx, auth, &id, body.into(), files).await?;
+            (EventAction::Update, json!(record))
+        }
+        Operation::Delete(id) => {
+            let record = record::delete_in::<T>(tx, auth, &id, files).await?;
+            return Ok(Outcome {
+                status: StatusCode::NO_CONTENT,
+                body: Value::Null,
+                action: EventAction::Delete,
+                record: json!(record),
+            });
+        }
+    };
+
+    Ok(Outcome {
+        status: StatusCode::OK,
+        body: record.clone(),
+        action,
+        record,
+    })
+}
+
+/// Reports the failed request under `requests.<index>`, with the response it
+/// would have had on its own. Internal errors are returned as is, so their
+/// details are only logged.
+fn request_failed(index: usize, err: Error) -> Error {
+    if err.status_code().is_server_error() {
+        return err;
+    }
+
+    let response = json!({
+        "status": err.status_code().as_u16(),
+        "message": err.to_string(),
+        "data": err.data(),
+    });
+    let mut requests = Map::new();
+    requests.insert(
+        index.to_string(),
+        json!({ "code": "batch_request_failed", "message": "Batch request failed.", "response": response }),
+    );
+    let mut data = Map::new();
+    data.insert("requests".to_string(), Value::Object(requests));
+
+    Error::BadRequest(BadRequest {
+        message: "Batch transaction failed.".to_string(),
+        data,
+    })
+}
@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, CookieJar, SameSite},
+    WithRejection,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::errors::Error;
+use crate::forms::cart::{AddCartItem, UpdateCartItem};
+use crate::forms::checkout::Checkout;
+use crate::models::cart::{self, CartOwner, SESSION_COOKIE, SESSION_DAYS};
+use crate::models::checkout;
+use crate::models::pocketbase::Order;
+use crate::models::realtime::{EventAction, Realtime};
+use crate::models::record::Record;
+use crate::settings::SETTINGS;
+use crate::utils::auth::{Auth, OptionalAuth};
+
+/// Resolves whose cart a request is about: the authenticated user, or the
+/// guest of the session cookie. Guests without a session have no cart yet.
+fn cart_owner(auth: Option<&Auth>, jar: &CookieJar) -> Result<Option<CartOwner>, Error> {
+    match auth {
+        Some(Auth::User(user)) => Ok(Some(CartOwner::User(user.id.clone()))),
+        // Superusers and API keys have no cart
+        Some(Auth::Superuser(_) | Auth::ApiKey(_)) => Err(Error::forbidden()),
+        None => Ok(jar
+            .get(SESSION_COOKIE)
+            .map(|cookie| CartOwner::Session(cookie.value().to_string()))),
+    }
+}
+
+fn session_cookie(session_id: String) -> Cookie<'static> {
+    Cookie::build((SESSION_COOKIE, session_id))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(SETTINGS.is_production())
+        .max_age(time::Duration::days(SESSION_DAYS))
+        .build()
+}
+
+/// Returns the current cart with its items.
+pub async fn view(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    jar: CookieJar,
+) -> Result<Json<Value>, Error> {
+    let owner = cart_owner(auth.as_ref(), &jar)?.ok_or_else(Error::not_found)?;
+    let mut conn = pool.acquire().await?;
+    let cart = cart::view(&mut conn, &owner).await?;
+    Ok(Json(json!(cart)))
+}
+
+/// Adds a product to the current cart. Guests are given a cart session
+/// cookie on their first item.
+pub async fn add_item(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    jar: CookieJar,
+    WithRejection(Json(form), _): WithRejection<Json<AddCartItem>, Error>,
+) -> Result<(CookieJar, Json<Value>), Error> {
+    form.validate()?;
+
+    let (owner, jar) = match cart_owner(auth.as_ref(), &jar)? {
+        Some(owner) => (owner, jar),
+        None => {
+            let session_id = cart::generate_session_id();
+            (CartOwner::Session(session_id.clone()), jar.add(session_cookie(session_id)))
+        }
+    };
+    let mut conn = pool.acquire().await?;
+    let item = cart::add_item(&mut conn, &owner, &form.product, form.quantity).await?;
+    Ok((jar, Json(json!(item))))
+}
+
+pub async fn update_item(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    jar: CookieJar,
+    Path(id): Path<String>,
+    WithRejection(Json(form), _): WithRejection<Json<UpdateCartItem>, Error>,
+) -> Result<Json<Value>, Error> {
+    form.validate()?;
+
+    let owner = cart_owner(auth.as_ref(), &jar)?.ok_or_else(Error::not_found)?;
+    let mut conn = pool.acquire().await?;
+    let item = cart::update_item(&mut conn, &owner, &id, form.quantity).await?;
+    Ok(Json(json!(item)))
+}
+
+pub async fn remove_item(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Error> {
+    let owner = cart_owner(auth.as_ref(), &jar)?.ok_or_else(Error::not_found)?;
+    let mut conn = pool.acquire().await?;
+    cart::remove_item(&mut conn, &owner, &id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Prices a cart of the authenticated user or of the guest session.
+pub async fn summary(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, Error> {
+    let owner = cart_owner(auth.as_ref(), &jar)?.ok_or_else(Error::not_found)?;
+    let mut conn = pool.acquire().await?;
+    let summary = cart::summary(&mut conn, &owner, &id).await?;
+    Ok(Json(json!(summary)))
+}
+
+/// Places orders for the items of a cart of the authenticated user, one per
+/// store.
+pub async fn checkout(
+    State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    auth: Auth,
+    Path(id): Path<String>,
+    WithRejection(Json(form), _): WithRejection<Json<Checkout>, Error>,
+) -> Result<Json<Value>, Error> {
+    form.validate()?;
+
+    let mut conn = pool.acquire().await?;
+    let placed = checkout::checkout(&mut conn, &auth, &id, form).await?;
+    for order in &placed.orders {
+        realtime.publish(Order::COLLECTION, EventAction::Create, json!(order));
+    }
+
+    Ok(Json(json!(placed)))
+}
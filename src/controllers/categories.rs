@@ -1,51 +1,60 @@
-use axum::{http::StatusCode, response::Json};
+use axum::response::Json;
 use serde_json::{Value, json};
 use sqlx::SqlitePool;
 
+use crate::errors::Error;
+use crate::forms::record::RecordData;
+use crate::models::pocketbase::Category;
+use crate::models::record;
+use crate::utils::auth::Auth;
+
+#[allow(clippy::too_many_arguments)]
 pub async fn list(
-    _pool: &SqlitePool,
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
     page: i32,
     per_page: i32,
     _offset: i32,
-    _sort: Option<String>,
-    _filter: Option<String>,
+    sort: Option<String>,
+    filter: Option<String>,
     _expand: Option<String>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "page": page,
-        "perPage": per_page,
-        "totalItems": 0,
-        "totalPages": 0,
-        "items": []
-    })))
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let list = record::list::<Category>(&mut conn, auth, page, per_page, sort.as_deref(), filter.as_deref()).await?;
+    Ok(Json(json!(list)))
 }
 
 pub async fn get(
-    _pool: &SqlitePool,
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
     id: &str,
     _expand: Option<String>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": id,
-        "name": "",
-        "slug": ""
-    })))
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::view::<Category>(&mut conn, auth, id).await?;
+    Ok(Json(json!(record)))
 }
 
-pub async fn create(_pool: &SqlitePool, _data: Value) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": "new_category_id",
-        "created": chrono::Utc::now().to_rfc3339()
-    })))
+pub async fn create(pool: &SqlitePool, auth: Option<&Auth>, data: RecordData) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::create::<Category>(&mut conn, auth, data).await?;
+    Ok(Json(json!(record)))
 }
 
-pub async fn update(_pool: &SqlitePool, id: &str, _data: Value) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": id,
-        "updated": chrono::Utc::now().to_rfc3339()
-    })))
+pub async fn update(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::update::<Category>(&mut conn, auth, id, data).await?;
+    Ok(Json(json!(record)))
 }
 
-pub async fn delete(_pool: &SqlitePool, _id: &str) -> Result<StatusCode, StatusCode> {
-    Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+/// Returns the deleted record.
+pub async fn delete(pool: &SqlitePool, auth: Option<&Auth>, id: &str) -> Result<Value, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::delete::<Category>(&mut conn, auth, id).await?;
+    Ok(json!(record))
+}
@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Extension, Path, Query, Request, State},
+    http::{header, HeaderValue},
+    response::{Json, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+
+use crate::db::ReadPool;
+use crate::errors::Error;
+use crate::models::file::{self, Thumb, STORAGE};
+use crate::models::pocketbase::{Category, Order, Product, User};
+use crate::utils::auth::{create_file_token, verify_file_token, Auth};
+
+/// Stored names are unique, so a file never changes once uploaded.
+const CACHE_CONTROL: &str = "public, max-age=2592000";
+
+/// Protected files must not be kept by shared caches, nor outlive the token.
+const PROTECTED_CACHE_CONTROL: &str = "private, no-store";
+
+#[derive(Deserialize)]
+pub struct FileQuery {
+    pub thumb: Option<String>,
+    /// File token, required by protected files.
+    pub token: Option<String>,
+}
+
+/// Mints a short-lived token to fetch protected files with the credentials
+/// of the request.
+pub async fn token(auth: Auth) -> Result<Json<Value>, Error> {
+    let token = create_file_token(&auth)?;
+    Ok(Json(json!({ "token": token })))
+}
+
+/// Serves a file of a record, or one of the thumbnail sizes listed by its
+/// field. Other sizes get the original file. Range and conditional requests
+/// are supported. Protected files need a `token` of someone allowed to view
+/// the record.
+pub async fn serve(
+    State(primary): State<SqlitePool>,
+    Extension(ReadPool(pool)): Extension<ReadPool>,
+    Path((collection, record_id, filename)): Path<(String, String, String)>,
+    Query(query): Query<FileQuery>,
+    request: Request,
+) -> Result<Response, Error> {
+    // An invalid or expired token is treated as none
+    let auth = match &query.token {
+        Some(token) => verify_file_token(&primary, token).await.ok(),
+        None => None,
+    };
+
+    let mut conn = pool.acquire().await?;
+    let auth = auth.as_ref();
+    let field = match collection.as_str() {
+        "users" => file::find_field::<User>(&mut conn, auth, &record_id, &filename).await?,
+        "categories" => file::find_field::<Category>(&mut conn, auth, &record_id, &filename).await?,
+        "products" => file::find_field::<Product>(&mut conn, auth, &record_id, &filename).await?,
+        "orders" => file::find_field::<Order>(&mut conn, auth, &record_id, &filename).await?,
+        _ => return Err(Error::not_found()),
+    };
+    drop(conn);
+
+    let thumb = query
+        .thumb
+        .filter(|thumb| field.thumbs.contains(&thumb.as_str()))
+        .and_then(|thumb| Thumb::parse(&thumb));
+    let mut response = STORAGE
+        .serve(&collection, &record_id, &filename, thumb, request)
+        .await?;
+    let cache_control = if field.protected { PROTECTED_CACHE_CONTROL } else { CACHE_CONTROL };
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+
+    Ok(response)
+}
@@ -1,4 +1,12 @@
 pub mod pocketbase;
 pub mod users;
 pub mod categories;
-pub mod stores;
\ No newline at end of file
+pub mod carts;
+pub mod stores;
+pub mod products;
+pub mod orders;
+pub mod api_keys;
+pub mod superusers;
+pub mod realtime;
+pub mod files;
+pub mod batch;
\ No newline at end of file
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Extension, Path, State},
+    response::Json,
+};
+use axum_extra::extract::WithRejection;
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::errors::Error;
+use crate::forms::order::ChangeOrderStatus;
+use crate::forms::record::RecordData;
+use crate::models::order_status;
+use crate::models::pocketbase::Order;
+use crate::models::realtime::{EventAction, Realtime};
+use crate::models::record::{self, Record};
+use crate::utils::auth::{Auth, OptionalAuth};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn list(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    page: i32,
+    per_page: i32,
+    _offset: i32,
+    sort: Option<String>,
+    filter: Option<String>,
+    _expand: Option<String>,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let list = record::list::<Order>(&mut conn, auth, page, per_page, sort.as_deref(), filter.as_deref()).await?;
+    Ok(Json(json!(list)))
+}
+
+pub async fn get(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    _expand: Option<String>,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::view::<Order>(&mut conn, auth, id).await?;
+    Ok(Json(json!(record)))
+}
+
+pub async fn create(pool: &SqlitePool, auth: Option<&Auth>, data: RecordData) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::create::<Order>(&mut conn, auth, data).await?;
+    Ok(Json(json!(record)))
+}
+
+pub async fn update(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::update::<Order>(&mut conn, auth, id, data).await?;
+    Ok(Json(json!(record)))
+}
+
+/// Returns the deleted record.
+pub async fn delete(pool: &SqlitePool, auth: Option<&Auth>, id: &str) -> Result<Value, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::delete::<Order>(&mut conn, auth, id).await?;
+    Ok(json!(record))
+}
+
+/// Moves an order to another status of its lifecycle.
+pub async fn change_status(
+    State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    auth: Auth,
+    Path(id): Path<String>,
+    WithRejection(Json(form), _): WithRejection<Json<ChangeOrderStatus>, Error>,
+) -> Result<Json<Value>, Error> {
+    form.validate()?;
+
+    let mut conn = pool.acquire().await?;
+    let order = order_status::transition(&mut conn, &auth, &id, form.status, form.note).await?;
+    realtime.publish(Order::COLLECTION, EventAction::Update, json!(order));
+
+    Ok(Json(json!(order)))
+}
+
+/// Lists the status changes of an order, oldest first.
+pub async fn history(
+    State(pool): State<SqlitePool>,
+    OptionalAuth(auth): OptionalAuth,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let changes = order_status::history(&mut conn, auth.as_ref(), &id).await?;
+    Ok(Json(json!({ "items": changes })))
+}
@@ -1,13 +1,20 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Extension, Path, Query, State},
     http::StatusCode,
     response::Json,
 };
+use axum_extra::extract::WithRejection;
 use serde_json::{Value, json};
 use sqlx::SqlitePool;
 use std::collections::HashMap;
 
-use super::{users, categories, stores};
+use crate::db::ReadPool;
+use crate::errors::Error;
+use crate::forms::record::RecordData;
+use crate::models::realtime::{EventAction, Realtime};
+use crate::utils::auth::OptionalAuth;
+
+use super::{users, categories, stores, products, orders};
 
 // Query parameters for list operations
 #[derive(serde::Deserialize)]
@@ -30,75 +37,102 @@ pub async fn health() -> Json<Value> {
 
 // List records for a collection
 pub async fn list_records(
-    State(pool): State<SqlitePool>,
+    Extension(ReadPool(pool)): Extension<ReadPool>,
+    OptionalAuth(auth): OptionalAuth,
     Path(collection): Path<String>,
-    Query(query): Query<ListQuery>,
-) -> Result<Json<Value>, StatusCode> {
+    WithRejection(Query(query), _): WithRejection<Query<ListQuery>, Error>,
+) -> Result<Json<Value>, Error> {
     let page = query.page.unwrap_or(1);
     let per_page = query.per_page.unwrap_or(30);
     let offset = (page - 1) * per_page;
 
     match collection.as_str() {
-        "users" => users::list(&pool, page, per_page, offset, query.sort, query.filter, query.expand).await,
-        "categories" => categories::list(&pool, page, per_page, offset, query.sort, query.filter, query.expand).await,
-        "stores" => stores::list(&pool, page, per_page, offset, query.sort, query.filter, query.expand).await,
-        _ => Err(StatusCode::NOT_FOUND),
+        "users" => users::list(&pool, auth.as_ref(), page, per_page, offset, query.sort, query.filter, query.expand).await,
+        "categories" => categories::list(&pool, auth.as_ref(), page, per_page, offset, query.sort, query.filter, query.expand).await,
+        "stores" => stores::list(&pool, auth.as_ref(), page, per_page, offset, query.sort, query.filter, query.expand).await,
+        "products" => products::list(&pool, auth.as_ref(), page, per_page, offset, query.sort, query.filter, query.expand).await,
+        "orders" => orders::list(&pool, auth.as_ref(), page, per_page, offset, query.sort, query.filter, query.expand).await,
+        _ => Err(Error::not_found()),
     }
 }
 
 // Get a specific record
 pub async fn get_record(
-    State(pool): State<SqlitePool>,
+    Extension(ReadPool(pool)): Extension<ReadPool>,
+    OptionalAuth(auth): OptionalAuth,
     Path((collection, id)): Path<(String, String)>,
     Query(query): Query<HashMap<String, String>>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, Error> {
     let expand = query.get("expand").cloned();
 
     match collection.as_str() {
-        "users" => users::get(&pool, &id, expand).await,
-        "categories" => categories::get(&pool, &id, expand).await,
-        "stores" => stores::get(&pool, &id, expand).await,
-        _ => Err(StatusCode::NOT_FOUND),
+        "users" => users::get(&pool, auth.as_ref(), &id, expand).await,
+        "categories" => categories::get(&pool, auth.as_ref(), &id, expand).await,
+        "stores" => stores::get(&pool, auth.as_ref(), &id, expand).await,
+        "products" => products::get(&pool, auth.as_ref(), &id, expand).await,
+        "orders" => orders::get(&pool, auth.as_ref(), &id, expand).await,
+        _ => Err(Error::not_found()),
     }
 }
 
-// Create a new record
+// Create a new record, from JSON or from a multipart form with files
 pub async fn create_record(
     State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    OptionalAuth(auth): OptionalAuth,
     Path(collection): Path<String>,
-    Json(data): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    match collection.as_str() {
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let record = match collection.as_str() {
         "users" => users::create(&pool, data).await,
-        "categories" => categories::create(&pool, data).await,
-        "stores" => stores::create(&pool, data).await,
-        _ => Err(StatusCode::NOT_FOUND),
-    }
+        "categories" => categories::create(&pool, auth.as_ref(), data).await,
+        "stores" => stores::create(&pool, auth.as_ref(), data).await,
+        "products" => products::create(&pool, auth.as_ref(), data).await,
+        "orders" => orders::create(&pool, auth.as_ref(), data).await,
+        _ => Err(Error::not_found()),
+    }?;
+
+    realtime.publish(&collection, EventAction::Create, record.0.clone());
+    Ok(record)
 }
 
 // Update an existing record
 pub async fn update_record(
     State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    OptionalAuth(auth): OptionalAuth,
     Path((collection, id)): Path<(String, String)>,
-    Json(data): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    match collection.as_str() {
-        "users" => users::update(&pool, &id, data).await,
-        "categories" => categories::update(&pool, &id, data).await,
-        "stores" => stores::update(&pool, &id, data).await,
-        _ => Err(StatusCode::NOT_FOUND),
-    }
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let record = match collection.as_str() {
+        "users" => users::update(&pool, auth.as_ref(), &id, data).await,
+        "categories" => categories::update(&pool, auth.as_ref(), &id, data).await,
+        "stores" => stores::update(&pool, auth.as_ref(), &id, data).await,
+        "products" => products::update(&pool, auth.as_ref(), &id, data).await,
+        "orders" => orders::update(&pool, auth.as_ref(), &id, data).await,
+        _ => Err(Error::not_found()),
+    }?;
+
+    realtime.publish(&collection, EventAction::Update, record.0.clone());
+    Ok(record)
 }
 
 // Delete a record
 pub async fn delete_record(
     State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    OptionalAuth(auth): OptionalAuth,
     Path((collection, id)): Path<(String, String)>,
-) -> Result<StatusCode, StatusCode> {
-    match collection.as_str() {
-        "users" => users::delete(&pool, &id).await,
-        "categories" => categories::delete(&pool, &id).await,
-        "stores" => stores::delete(&pool, &id).await,
-        _ => Err(StatusCode::NOT_FOUND),
-    }
-}
\ No newline at end of file
+) -> Result<StatusCode, Error> {
+    let record = match collection.as_str() {
+        "users" => users::delete(&pool, auth.as_ref(), &id).await,
+        "categories" => categories::delete(&pool, auth.as_ref(), &id).await,
+        "stores" => stores::delete(&pool, auth.as_ref(), &id).await,
+        "products" => products::delete(&pool, auth.as_ref(), &id).await,
+        "orders" => orders::delete(&pool, auth.as_ref(), &id).await,
+        _ => Err(Error::not_found()),
+    }?;
+
+    realtime.publish(&collection, EventAction::Delete, record);
+    Ok(StatusCode::NO_CONTENT)
+}
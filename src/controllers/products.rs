@@ -0,0 +1,60 @@
+use axum::response::Json;
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+
+use crate::errors::Error;
+use crate::forms::record::RecordData;
+use crate::models::pocketbase::Product;
+use crate::models::record;
+use crate::utils::auth::Auth;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn list(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    page: i32,
+    per_page: i32,
+    _offset: i32,
+    sort: Option<String>,
+    filter: Option<String>,
+    _expand: Option<String>,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let list = record::list::<Product>(&mut conn, auth, page, per_page, sort.as_deref(), filter.as_deref()).await?;
+    Ok(Json(json!(list)))
+}
+
+pub async fn get(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    _expand: Option<String>,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::view::<Product>(&mut conn, auth, id).await?;
+    Ok(Json(json!(record)))
+}
+
+pub async fn create(pool: &SqlitePool, auth: Option<&Auth>, data: RecordData) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::create::<Product>(&mut conn, auth, data).await?;
+    Ok(Json(json!(record)))
+}
+
+pub async fn update(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::update::<Product>(&mut conn, auth, id, data).await?;
+    Ok(Json(json!(record)))
+}
+
+/// Returns the deleted record.
+pub async fn delete(pool: &SqlitePool, auth: Option<&Auth>, id: &str) -> Result<Value, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::delete::<Product>(&mut conn, auth, id).await?;
+    Ok(json!(record))
+}
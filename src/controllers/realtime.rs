@@ -0,0 +1,301 @@
+use axum::{
+    extract::ws::{CloseFrame, Message as WsMessage, WebSocket, WebSocketUpgrade},
+    extract::{Extension, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    response::{Json, Response},
+};
+use axum_extra::extract::WithRejection;
+use chrono::Utc;
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::time::{interval, sleep_until, timeout, Instant};
+use tracing::error;
+use validator::Validate;
+
+use crate::errors::{AuthenticateError, Error};
+use crate::forms::realtime::SetSubscriptions;
+use crate::models::realtime::{self, Message, Realtime, Subscription};
+use crate::models::record;
+use crate::utils::auth::{verify_access_token, Auth, OptionalAuth};
+
+/// WebSocket clients must authenticate within this delay.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// WebSocket clients are pinged at this interval and disconnected when
+/// nothing was received from them for two intervals.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+const CLOSE_POLICY_VIOLATION: u16 = 1008;
+const CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+/// Removes the client when its connection is closed.
+struct Connection {
+    realtime: Realtime,
+    client_id: String,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.realtime.disconnect(&self.client_id);
+    }
+}
+
+fn event(message: Message) -> Result<Event, Infallible> {
+    Ok(Event::default().event(message.name).data(message.data.to_string()))
+}
+
+/// Opens the event stream. The first event, `PB_CONNECT`, carries the id used
+/// to manage the subscriptions of the connection.
+pub async fn connect(
+    Extension(realtime): Extension<Realtime>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (client_id, receiver) = realtime.connect();
+    let connected = Event::default()
+        .id(&client_id)
+        .event("PB_CONNECT")
+        .data(json!({ "clientId": client_id }).to_string());
+    let connection = Connection { realtime, client_id };
+
+    let messages = stream::unfold((receiver, connection), |(mut receiver, connection)| async move {
+        let message = receiver.recv().await?;
+        Some((event(message), (receiver, connection)))
+    });
+
+    Sse::new(stream::once(async { Ok(connected) }).chain(messages)).keep_alive(KeepAlive::default())
+}
+
+/// Sets the subscriptions of a connected client, e.g. `products/*` or
+/// `orders/RECORD_ID?options={"query":{"filter":"status = 'paid'"}}`.
+pub async fn set_subscriptions(
+    State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    OptionalAuth(auth): OptionalAuth,
+    WithRejection(Json(form), _): WithRejection<Json<SetSubscriptions>, Error>,
+) -> Result<StatusCode, Error> {
+    form.validate()?;
+
+    let subscriptions = parse_subscriptions(&pool, form.subscriptions).await?;
+    realtime.subscribe(&form.client_id, auth, subscriptions)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn parse_subscriptions(pool: &SqlitePool, topics: Vec<String>) -> Result<Vec<(String, Subscription)>, Error> {
+    let mut conn = pool.acquire().await?;
+    let mut subscriptions = Vec::with_capacity(topics.len());
+    for topic in topics {
+        let subscription = Subscription::parse(&topic).ok_or_else(|| {
+            Error::invalid_field(
+                "subscriptions",
+                "validation_invalid_value",
+                &format!("Invalid subscription {topic:?}."),
+            )
+        })?;
+        if let Some(filter) = &subscription.filter {
+            realtime::check_filter(&mut conn, &subscription.collection, filter).await?;
+        }
+        subscriptions.push((topic, subscription));
+    }
+
+    Ok(subscriptions)
+}
+
+/// Messages sent by WebSocket clients.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Command {
+    /// Must be the first message. Connections without a token are anonymous.
+    Auth { token: Option<String> },
+    /// Adds subscriptions.
+    Subscribe { subscriptions: Vec<String> },
+    /// Removes subscriptions, or all of them when none are given.
+    Unsubscribe {
+        #[serde(default)]
+        subscriptions: Vec<String>,
+    },
+}
+
+/// Same protocol as the event stream over a WebSocket, for clients behind
+/// proxies that buffer Server-Sent Events.
+///
+/// The client first sends `{"type": "auth", "token": "..."}` and receives
+/// `{"type": "connect", "clientId": "..."}`. It then sends `subscribe` and
+/// `unsubscribe` messages with a `subscriptions` list, acknowledged with the
+/// resulting `{"type": "subscriptions", ...}`, and receives
+/// `{"type": "event", "name": "products/*", "data": {...}}` messages.
+pub async fn websocket(
+    State(pool): State<SqlitePool>,
+    Extension(realtime): Extension<Realtime>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve_websocket(socket, pool, realtime))
+}
+
+async fn serve_websocket(mut socket: WebSocket, pool: SqlitePool, realtime: Realtime) {
+    let auth = match timeout(AUTH_TIMEOUT, authenticate(&mut socket)).await {
+        Ok(Ok(auth)) => auth,
+        Ok(Err(err)) => {
+            let _ = socket.send(error_message(&err)).await;
+            let _ = socket.send(close(CLOSE_POLICY_VIOLATION, "Authentication failed")).await;
+            return;
+        }
+        Err(_) => {
+            let _ = socket.send(close(CLOSE_POLICY_VIOLATION, "Authentication timed out")).await;
+            return;
+        }
+    };
+
+    let (client_id, mut receiver) = realtime.connect();
+    let _connection = Connection {
+        realtime: realtime.clone(),
+        client_id: client_id.clone(),
+    };
+    if socket
+        .send(text(json!({ "type": "connect", "clientId": client_id })))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    // The token is only checked once, so the connection ends when it expires
+    let expiry = auth.as_ref().and_then(Auth::expires_at).map(|expires_at| {
+        let remaining = u64::try_from(expires_at - Utc::now().timestamp()).unwrap_or(0);
+        Instant::now() + Duration::from_secs(remaining)
+    });
+    let token_expired = sleep_until(expiry.unwrap_or_else(Instant::now));
+    tokio::pin!(token_expired);
+
+    let mut topics: Vec<String> = Vec::new();
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    loop {
+        tokio::select! {
+            message = receiver.recv() => {
+                let Some(message) = message else {
+                    // Dropped by the realtime system for falling behind
+                    let _ = socket.send(close(CLOSE_TRY_AGAIN_LATER, "Too many pending events")).await;
+                    return;
+                };
+                let message = json!({ "type": "event", "name": message.name, "data": message.data });
+                if socket.send(text(message)).await.is_err() {
+                    return;
+                }
+            }
+            incoming = socket.recv() => {
+                let Some(Ok(incoming)) = incoming else {
+                    return;
+                };
+                last_seen = Instant::now();
+                let WsMessage::Text(incoming) = incoming else {
+                    if matches!(incoming, WsMessage::Close(_)) {
+                        return;
+                    }
+                    continue;
+                };
+
+                let reply = match update_topics(&pool, &realtime, &client_id, auth.clone(), &topics, &incoming).await {
+                    Ok(updated) => {
+                        topics = updated;
+                        text(json!({ "type": "subscriptions", "subscriptions": topics }))
+                    }
+                    Err(err) => error_message(&err),
+                };
+                if socket.send(reply).await.is_err() {
+                    return;
+                }
+            }
+            _ = &mut token_expired, if expiry.is_some() => {
+                let _ = socket.send(close(CLOSE_POLICY_VIOLATION, "Token expired")).await;
+                return;
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > HEARTBEAT_INTERVAL * 2 {
+                    let _ = socket.send(close(CLOSE_POLICY_VIOLATION, "Heartbeat timed out")).await;
+                    return;
+                }
+                if socket.send(WsMessage::Ping(Vec::new())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Waits for the `auth` message.
+async fn authenticate(socket: &mut WebSocket) -> Result<Option<Auth>, Error> {
+    loop {
+        let Some(Ok(message)) = socket.recv().await else {
+            return Err(AuthenticateError::InvalidToken.into());
+        };
+        let WsMessage::Text(message) = message else {
+            continue;
+        };
+
+        return match serde_json::from_str::<Command>(&message).map_err(|_| record::invalid_formatting())? {
+            Command::Auth { token: None } => Ok(None),
+            Command::Auth { token: Some(token) } => verify_access_token(&token).map(Some),
+            _ => Err(AuthenticateError::InvalidToken.into()),
+        };
+    }
+}
+
+/// Applies a `subscribe` or `unsubscribe` command and returns the resulting
+/// topics.
+async fn update_topics(
+    pool: &SqlitePool,
+    realtime: &Realtime,
+    client_id: &str,
+    auth: Option<Auth>,
+    topics: &[String],
+    command: &str,
+) -> Result<Vec<String>, Error> {
+    let mut updated = topics.to_vec();
+    match serde_json::from_str::<Command>(command).map_err(|_| record::invalid_formatting())? {
+        Command::Subscribe { subscriptions } => {
+            for topic in subscriptions {
+                if !updated.contains(&topic) {
+                    updated.push(topic);
+                }
+            }
+        }
+        Command::Unsubscribe { subscriptions } if subscriptions.is_empty() => updated.clear(),
+        Command::Unsubscribe { subscriptions } => updated.retain(|topic| !subscriptions.contains(topic)),
+        Command::Auth { .. } => return Err(Error::forbidden()),
+    }
+
+    let subscriptions = parse_subscriptions(pool, updated.clone()).await?;
+    realtime.subscribe(client_id, auth, subscriptions)?;
+
+    Ok(updated)
+}
+
+fn text(message: Value) -> WsMessage {
+    WsMessage::Text(message.to_string())
+}
+
+fn close(code: u16, reason: &'static str) -> WsMessage {
+    WsMessage::Close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }))
+}
+
+/// Errors in the shape of the HTTP API ones. As there, the details of
+/// internal errors are only logged.
+fn error_message(err: &Error) -> WsMessage {
+    let status = err.status_code();
+    let message = if status.is_server_error() {
+        error!(error = %err, "Internal server error in a realtime connection");
+        "Something went wrong while processing your request.".to_string()
+    } else {
+        err.to_string()
+    };
+
+    text(json!({ "type": "error", "status": status.as_u16(), "message": message, "data": err.data() }))
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::Duration;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tracing::info;
+use validator::Validate;
+
+use crate::errors::{AuthenticateError, Error};
+use crate::forms::auth::AuthWithPassword;
+use crate::forms::superuser::Impersonate;
+use crate::models::audit::{self, AuditLog};
+use crate::models::auth::AuthModel;
+use crate::models::pocketbase::{PBAuthResponse, User};
+use crate::models::superuser::Superuser;
+use crate::models::user::check_password;
+use crate::utils::auth::Auth;
+
+const DEFAULT_IMPERSONATION_SECONDS: i64 = 15 * 60;
+
+pub async fn auth_with_password(
+    State(pool): State<SqlitePool>,
+    WithRejection(Json(form), _): WithRejection<Json<AuthWithPassword>, Error>,
+) -> Result<Json<PBAuthResponse<Superuser>>, Error> {
+    form.validate()?;
+
+    let superuser = sqlx::query_as::<_, Superuser>("SELECT * FROM _superusers WHERE email = ?")
+        .bind(&form.identity)
+        .fetch_optional(&pool)
+        .await?;
+
+    check_password(
+        &pool,
+        "_superusers",
+        superuser
+            .as_ref()
+            .map(|superuser| (superuser.id.as_str(), superuser.password_hash.as_str())),
+        form.password,
+    )
+    .await?;
+    let superuser = superuser.ok_or(AuthenticateError::WrongCredentials)?;
+
+    let token = AuthModel::new()
+        .create_superuser_token(&superuser.id)
+        .map_err(|_| AuthenticateError::TokenCreation)?;
+
+    Ok(Json(PBAuthResponse {
+        token,
+        record: superuser,
+    }))
+}
+
+/// Mints a non-refreshable token letting a superuser act as the given user.
+pub async fn impersonate(
+    State(pool): State<SqlitePool>,
+    auth: Auth,
+    Path(id): Path<String>,
+    form: Option<Json<Impersonate>>,
+) -> Result<Json<PBAuthResponse<User>>, Error> {
+    let superuser = auth.superuser().ok_or_else(Error::forbidden)?;
+    let form = form.map(|Json(form)| form).unwrap_or_default();
+    form.validate()?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(Error::not_found)?;
+
+    let seconds = form.duration.unwrap_or(DEFAULT_IMPERSONATION_SECONDS);
+    let token = AuthModel::new()
+        .create_impersonation_token(&user.id, &superuser.id, Duration::seconds(seconds))
+        .map_err(|_| AuthenticateError::TokenCreation)?;
+
+    AuditLog::new(
+        &superuser.id,
+        audit::IMPERSONATE,
+        Some(&user.id),
+        json!({ "duration": seconds }),
+    )
+    .insert(&pool)
+    .await?;
+    info!(impersonator = %superuser.id, user = %user.id, "Issued impersonation token");
+
+    Ok(Json(PBAuthResponse {
+        token,
+        record: user,
+    }))
+}
@@ -1,52 +1,201 @@
-use axum::{http::StatusCode, response::Json};
+use axum::{extract::State, response::Json};
+use axum_extra::extract::{
+    cookie::{Cookie, CookieJar},
+    WithRejection,
+};
 use serde_json::{Value, json};
 use sqlx::SqlitePool;
+use validator::Validate;
 
+use crate::errors::{AuthenticateError, Error};
+use crate::forms::auth::AuthWithPassword;
+use crate::forms::record::RecordData;
+use crate::forms::user::CreateUser;
+use crate::models::auth::AuthModel;
+use crate::models::cart::{self, SESSION_COOKIE};
+use crate::models::file::{self, STORAGE};
+use crate::models::password::PASSWORD_POLICY;
+use crate::models::pocketbase::{generate_id, validate_id, PBAuthResponse, User};
+use crate::models::record::{self, Record};
+use crate::models::user::{check_password, hash_password};
+use crate::utils::auth::Auth;
+
+#[derive(sqlx::FromRow)]
+struct UserCredentials {
+    #[sqlx(flatten)]
+    user: User,
+    password_hash: String,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn list(
-    _pool: &SqlitePool,
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
     page: i32,
     per_page: i32,
     _offset: i32,
-    _sort: Option<String>,
-    _filter: Option<String>,
+    sort: Option<String>,
+    filter: Option<String>,
     _expand: Option<String>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "page": page,
-        "perPage": per_page,
-        "totalItems": 0,
-        "totalPages": 0,
-        "items": []
-    })))
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let list = record::list::<User>(&mut conn, auth, page, per_page, sort.as_deref(), filter.as_deref()).await?;
+    Ok(Json(json!(list)))
 }
 
 pub async fn get(
-    _pool: &SqlitePool,
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
     id: &str,
     _expand: Option<String>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": id,
-        "email": "",
-        "username": "",
-        "name": ""
-    })))
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::view::<User>(&mut conn, auth, id).await?;
+    Ok(Json(json!(record)))
 }
 
-pub async fn create(_pool: &SqlitePool, _data: Value) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": "new_user_id",
-        "created": chrono::Utc::now().to_rfc3339()
-    })))
+/// Registers a user. Unlike other collections the password is accepted and
+/// stored as a hash.
+pub async fn create(pool: &SqlitePool, data: RecordData) -> Result<Json<Value>, Error> {
+    let (mut data, files) = {
+        let mut conn = pool.acquire().await?;
+        record::form_values::<User>(&mut conn, data, true).await?
+    };
+    let files = file::apply(User::FILE_FIELDS, &mut data, None, files)?;
+    let avatar = data.get("avatar").and_then(Value::as_str).map(str::to_string);
+    let form: CreateUser = serde_json::from_value(Value::Object(data)).map_err(|_| record::invalid_formatting())?;
+    form.validate()?;
+    PASSWORD_POLICY.check(&form.password)?;
+    if form.password != form.password_confirm {
+        return Err(Error::invalid_field(
+            "passwordConfirm",
+            "validation_values_mismatch",
+            "Values don't match.",
+        ));
+    }
+
+    let password_hash = hash_password(form.password).await?;
+
+    let mut user = User::new(form.email, form.username, form.name);
+    user.email_visibility = form.email_visibility;
+    user.avatar = avatar;
+    let generated_id = match form.id.filter(|id| !id.is_empty()) {
+        Some(id) => {
+            validate_id(&id)?;
+            user.id = id;
+            false
+        }
+        None => true,
+    };
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match insert(pool, &user, &password_hash).await {
+            Ok(()) => break,
+            Err(err) if generated_id && attempts < record::ID_ATTEMPTS && record::is_id_taken(&err) => {
+                user.id = generate_id();
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if let Err(err) = STORAGE.save(User::COLLECTION, &user.id, &files).await {
+        sqlx::query("DELETE FROM users WHERE id = ?").bind(&user.id).execute(pool).await?;
+        return Err(err);
+    }
+
+    Ok(Json(json!(user)))
 }
 
-pub async fn update(_pool: &SqlitePool, id: &str, _data: Value) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "id": id,
-        "updated": chrono::Utc::now().to_rfc3339()
-    })))
+async fn insert(pool: &SqlitePool, user: &User, password_hash: &str) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO users (id, email, email_visibility, username, name, avatar, verified, password_hash, created, updated, collection_id, collection_name) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&user.id)
+    .bind(&user.email)
+    .bind(user.email_visibility)
+    .bind(&user.username)
+    .bind(&user.name)
+    .bind(&user.avatar)
+    .bind(user.verified)
+    .bind(password_hash)
+    .bind(&user.created)
+    .bind(&user.updated)
+    .bind(&user.collection_id)
+    .bind(&user.collection_name)
+    .execute(pool)
+    .await?;
+
+    Ok(())
 }
 
-pub async fn delete(_pool: &SqlitePool, _id: &str) -> Result<StatusCode, StatusCode> {
-    Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+pub async fn update(
+    pool: &SqlitePool,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+) -> Result<Json<Value>, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::update::<User>(&mut conn, auth, id, data).await?;
+    Ok(Json(json!(record)))
+}
+
+/// Returns the deleted record.
+pub async fn delete(pool: &SqlitePool, auth: Option<&Auth>, id: &str) -> Result<Value, Error> {
+    let mut conn = pool.acquire().await?;
+    let record = record::delete::<User>(&mut conn, auth, id).await?;
+    Ok(json!(record))
+}
+
+/// Signs a user in. A guest cart of the request is merged into the user's
+/// cart, and its session cookie removed.
+pub async fn auth_with_password(
+    State(pool): State<SqlitePool>,
+    jar: CookieJar,
+    WithRejection(Json(form), _): WithRejection<Json<AuthWithPassword>, Error>,
+) -> Result<(CookieJar, Json<PBAuthResponse<User>>), Error> {
+    form.validate()?;
+
+    let credentials = sqlx::query_as::<_, UserCredentials>(
+        "SELECT * FROM users WHERE email = ? OR username = ? LIMIT 1",
+    )
+    .bind(&form.identity)
+    .bind(&form.identity)
+    .fetch_optional(&pool)
+    .await?;
+
+    check_password(
+        &pool,
+        "users",
+        credentials
+            .as_ref()
+            .map(|credentials| (credentials.user.id.as_str(), credentials.password_hash.as_str())),
+        form.password,
+    )
+    .await?;
+    let user = credentials
+        .map(|credentials| credentials.user)
+        .ok_or(AuthenticateError::WrongCredentials)?;
+
+    let token = AuthModel::new()
+        .create_token(&user.id)
+        .map_err(|_| AuthenticateError::TokenCreation)?;
+
+    let jar = match jar.get(SESSION_COOKIE).map(|cookie| cookie.value().to_string()) {
+        Some(session_id) => {
+            let mut conn = pool.acquire().await?;
+            cart::merge_guest_cart(&mut conn, &session_id, &user.id).await?;
+            jar.remove(Cookie::build(SESSION_COOKIE).path("/"))
+        }
+        None => jar,
+    };
+
+    Ok((
+        jar,
+        Json(PBAuthResponse {
+            token: token.access_token,
+            record: user,
+        }),
+    ))
+}
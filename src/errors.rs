@@ -1,15 +1,23 @@
-use axum::http::StatusCode;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::http::{HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use bcrypt::BcryptError;
-use serde_json::json;
+use serde_json::{json, Map, Value};
+use sqlx::error::ErrorKind;
 use tokio::task::JoinError;
+use tracing::error;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+/// Response header carrying the id internal errors are logged with.
+const CORRELATION_ID_HEADER: &str = "x-correlation-id";
 
 #[derive(thiserror::Error, Debug)]
 #[error("...")]
 pub enum Error {
     #[error("{0}")]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(sqlx::Error),
 
     #[error("{0}")]
     Anyhow(#[from] anyhow::Error),
@@ -20,64 +28,245 @@ pub enum Error {
     #[error("{0}")]
     BadRequest(#[from] BadRequest),
 
+    #[error("Failed to validate the submitted data.")]
+    Validation(#[from] ValidationErrors),
+
     #[error("{0}")]
     NotFound(#[from] NotFound),
 
+    #[error("{0}")]
+    Forbidden(#[from] Forbidden),
+
+    #[error("{0}")]
+    Conflict(#[from] Conflict),
+
     #[error("{0}")]
     RunSyncTask(#[from] JoinError),
 
     #[error("{0}")]
-    HashPassword(#[from] BcryptError),
+    HashPassword(#[from] HashPasswordError),
 
     #[error("Internal Server Error")]
     InternalServerError,
 }
 
 impl Error {
-    fn get_codes(&self) -> (StatusCode, u16) {
+    pub fn status_code(&self) -> StatusCode {
         match *self {
             // 4XX Errors
-            Error::BadRequest(_) => (StatusCode::BAD_REQUEST, 40002),
-            Error::NotFound(_) => (StatusCode::NOT_FOUND, 40003),
-            Error::Authenticate(AuthenticateError::WrongCredentials) => {
-                (StatusCode::UNAUTHORIZED, 40004)
-            }
-            Error::Authenticate(AuthenticateError::InvalidToken) => {
-                (StatusCode::UNAUTHORIZED, 40005)
-            }
-            Error::Authenticate(AuthenticateError::Locked) => (StatusCode::LOCKED, 40006),
+            Error::BadRequest(_) | Error::Validation(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Authenticate(AuthenticateError::WrongCredentials) => StatusCode::UNAUTHORIZED,
+            Error::Authenticate(AuthenticateError::InvalidToken) => StatusCode::UNAUTHORIZED,
+            Error::Authenticate(AuthenticateError::Locked) => StatusCode::LOCKED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::Conflict(_) => StatusCode::CONFLICT,
 
             // 5XX Errors
-            Error::Authenticate(AuthenticateError::TokenCreation) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, 5001)
-            }
-            Error::Sqlx(_) => (StatusCode::INTERNAL_SERVER_ERROR, 5002),
-            Error::Anyhow(_) => (StatusCode::INTERNAL_SERVER_ERROR, 5003),
-            Error::RunSyncTask(_) => (StatusCode::INTERNAL_SERVER_ERROR, 5005),
-            Error::HashPassword(_) => (StatusCode::INTERNAL_SERVER_ERROR, 5006),
-            Error::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, 5007),
+            Error::Authenticate(AuthenticateError::TokenCreation)
+            | Error::Sqlx(_)
+            | Error::Anyhow(_)
+            | Error::RunSyncTask(_)
+            | Error::HashPassword(_)
+            | Error::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Per field `{code, message}` details, in the shape the PocketBase SDK
+    /// expects.
+    pub fn data(&self) -> Map<String, Value> {
+        match self {
+            Error::BadRequest(bad_request) => bad_request.data.clone(),
+            Error::Validation(errors) => validation_data(errors),
+            _ => Map::new(),
         }
     }
 
-    pub fn bad_request() -> Self {
-        Error::BadRequest(BadRequest {})
+    /// A validation failure of a single field.
+    pub fn invalid_field(field: &str, code: &str, message: &str) -> Self {
+        let mut data = Map::new();
+        data.insert(field.to_string(), json!({ "code": code, "message": message }));
+
+        Error::BadRequest(BadRequest {
+            message: "Failed to validate the submitted data.".to_string(),
+            data,
+        })
     }
 
     pub fn not_found() -> Self {
         Error::NotFound(NotFound {})
     }
+
+    pub fn forbidden() -> Self {
+        Error::Forbidden(Forbidden {})
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        let (status_code, code) = self.get_codes();
-        let message = self.to_string();
-        let body = Json(json!({ "code": code, "message": message }));
+        let status_code = self.status_code();
+
+        // Internal errors may contain SQL or other implementation details, so
+        // they are only logged. The correlation id lets a client report link
+        // back to the log entry.
+        if status_code.is_server_error() {
+            let correlation_id = Uuid::new_v4().to_string();
+            error!(%correlation_id, error = %self, "Internal server error");
+
+            let body = Json(json!({
+                "status": status_code.as_u16(),
+                "message": "Something went wrong while processing your request.",
+                "data": {},
+            }));
+            let mut response = (status_code, body).into_response();
+            if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+                response.headers_mut().insert(CORRELATION_ID_HEADER, value);
+            }
+            return response;
+        }
+
+        let body = Json(json!({
+            "status": status_code.as_u16(),
+            "message": self.to_string(),
+            "data": self.data(),
+        }));
 
         (status_code, body).into_response()
     }
 }
 
+/// Translates constraint violations into validation errors naming the
+/// offending column. Anything else is treated as an internal error.
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        let Some(db_err) = err.as_database_error() else {
+            return Error::Sqlx(err);
+        };
+        let field = constraint_column(db_err.message());
+
+        match (db_err.kind(), field) {
+            (ErrorKind::UniqueViolation, Some(field)) => {
+                Error::invalid_field(&field, "validation_not_unique", "Value is already in use.")
+            }
+            (ErrorKind::NotNullViolation, Some(field)) => {
+                Error::invalid_field(&field, "validation_required", "Missing required value.")
+            }
+            (ErrorKind::CheckViolation, Some(field)) => {
+                Error::invalid_field(&field, "validation_invalid_value", "Invalid value.")
+            }
+            _ if is_foreign_key_violation(&err) => Error::BadRequest(BadRequest::new(
+                "Failed to find all relation records with the provided ids.",
+            )),
+            _ => Error::Sqlx(err),
+        }
+    }
+}
+
+impl From<PasswordPolicyError> for Error {
+    fn from(err: PasswordPolicyError) -> Self {
+        Error::invalid_field("password", err.code(), &err.to_string())
+    }
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::BadRequest(BadRequest::new(rejection.body_text()))
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::BadRequest(BadRequest::new(rejection.body_text()))
+    }
+}
+
+/// `ON DELETE RESTRICT` violations are reported by SQLite with the trigger
+/// constraint code rather than the foreign key one, hence the message check.
+pub fn is_foreign_key_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error().is_some_and(|err| {
+        err.kind() == ErrorKind::ForeignKeyViolation
+            || err.message().starts_with("FOREIGN KEY constraint failed")
+    })
+}
+
+/// Extracts the column from SQLite constraint messages such as
+/// `UNIQUE constraint failed: stores.slug` or
+/// `CHECK constraint failed: quantity >= 1`.
+fn constraint_column(message: &str) -> Option<String> {
+    let (_, detail) = message.split_once("constraint failed: ")?;
+    let first = detail.split(", ").next()?;
+    let column = first.rsplit_once('.').map_or(first, |(_, column)| column);
+    let column: String = column
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+
+    (!column.is_empty()).then_some(column)
+}
+
+fn validation_data(errors: &ValidationErrors) -> Map<String, Value> {
+    errors
+        .errors()
+        .iter()
+        .filter_map(|(field, kind)| {
+            let value = match kind {
+                ValidationErrorsKind::Field(errors) => field_error(errors.first()?),
+                ValidationErrorsKind::Struct(errors) => Value::Object(validation_data(errors)),
+                ValidationErrorsKind::List(items) => Value::Object(
+                    items
+                        .iter()
+                        .map(|(index, errors)| (index.to_string(), Value::Object(validation_data(errors))))
+                        .collect(),
+                ),
+            };
+            Some((field.to_string(), value))
+        })
+        .collect()
+}
+
+/// Maps a validator error to PocketBase's error codes and messages.
+fn field_error(error: &ValidationError) -> Value {
+    let param = |name: &str| error.params.get(name).map(Value::to_string);
+    let is_empty = error
+        .params
+        .get("value")
+        .is_some_and(|value| value.as_str() == Some("") || value.is_null());
+
+    let (code, message) = match error.code.as_ref() {
+        "required" => ("validation_required".to_string(), "Missing required value.".to_string()),
+        "length" if is_empty => ("validation_required".to_string(), "Missing required value.".to_string()),
+        "length" | "range" => {
+            let message = match (param("min"), param("max")) {
+                (Some(min), Some(max)) => format!("Must be between {min} and {max}."),
+                (Some(min), None) => format!("Must be at least {min}."),
+                (None, Some(max)) => format!("Must be no more than {max}."),
+                (None, None) => "Invalid value.".to_string(),
+            };
+            let code = if error.code == "length" {
+                "validation_length_out_of_range"
+            } else {
+                "validation_out_of_range"
+            };
+            (code.to_string(), message)
+        }
+        "email" => (
+            "validation_invalid_email".to_string(),
+            "Must be a valid email address.".to_string(),
+        ),
+        "must_match" => ("validation_values_mismatch".to_string(), "Values don't match.".to_string()),
+        code => (format!("validation_{code}"), "Invalid value.".to_string()),
+    };
+
+    let message = error
+        .message
+        .as_ref()
+        .map(|message| message.to_string())
+        .unwrap_or(message);
+
+    json!({ "code": code, "message": message })
+}
+
 #[derive(thiserror::Error, Debug)]
 #[error("...")]
 pub enum AuthenticateError {
@@ -92,9 +281,71 @@ pub enum AuthenticateError {
 }
 
 #[derive(thiserror::Error, Debug)]
-#[error("Bad Request")]
-pub struct BadRequest {}
+#[error("...")]
+pub enum HashPasswordError {
+    #[error("{0}")]
+    Bcrypt(#[from] BcryptError),
+    #[error("{0}")]
+    Argon2(#[from] argon2::password_hash::Error),
+    #[error("Unsupported password hash format")]
+    UnknownFormat,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("...")]
+pub enum PasswordPolicyError {
+    #[error("Must be at least {0} character(s).")]
+    TooShort(usize),
+    #[error("Must be no more than {0} character(s).")]
+    TooLong(usize),
+    #[error("This password has appeared in a data breach, please choose another one.")]
+    Breached,
+}
+
+impl PasswordPolicyError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PasswordPolicyError::TooShort(_) => "validation_min_text_constraint",
+            PasswordPolicyError::TooLong(_) => "validation_max_text_constraint",
+            PasswordPolicyError::Breached => "validation_breached_password",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{message}")]
+pub struct BadRequest {
+    pub message: String,
+    pub data: Map<String, Value>,
+}
+
+impl BadRequest {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            data: Map::new(),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{message}")]
+pub struct Conflict {
+    pub message: String,
+}
+
+impl Conflict {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 #[error("Not found")]
-pub struct NotFound {}
\ No newline at end of file
+pub struct NotFound {}
+
+#[derive(thiserror::Error, Debug)]
+#[error("You are not allowed to perform this request")]
+pub struct Forbidden {}
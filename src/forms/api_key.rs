@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::models::api_key::ApiKeyScope;
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CreateApiKey {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<ApiKeyScope>,
+}
@@ -5,4 +5,13 @@ use validator::Validate;
 pub struct Token {
     #[validate(length(min = 1, message = "Refresh token is required"))]
     pub refresh_token: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AuthWithPassword {
+    /// Email or username.
+    #[validate(length(min = 1, message = "Identity is required"))]
+    pub identity: String,
+    #[validate(length(min = 1, message = "Password is required"))]
+    pub password: String,
+}
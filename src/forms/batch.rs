@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::Validate;
+
+/// Largest number of requests a batch may hold.
+pub const MAX_BATCH_REQUESTS: u64 = 50;
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct Batch {
+    #[validate(length(min = 1, max = MAX_BATCH_REQUESTS))]
+    pub requests: Vec<BatchRequest>,
+}
+
+/// A record request, in the shape of the corresponding API call, e.g.
+/// `{"method": "PATCH", "url": "/api/collections/products/records/ID", "body": {...}}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub body: Value,
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+fn default_quantity() -> i64 {
+    1
+}
+
+/// Adds `quantity` of `product` to the current cart, on top of the quantity
+/// already in it.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AddCartItem {
+    #[validate(length(min = 1))]
+    pub product: String,
+    #[serde(default = "default_quantity")]
+    #[validate(range(min = 1, max = 1000))]
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateCartItem {
+    #[validate(range(min = 1, max = 1000))]
+    pub quantity: i64,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// Delivery details of a checkout. `name` and `email` default to those of
+/// the buyer.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct Checkout {
+    #[validate(length(min = 1))]
+    pub address: String,
+    #[validate(length(min = 1))]
+    pub name: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    pub notes: Option<String>,
+}
@@ -1,2 +1,11 @@
+pub mod api_key;
 pub mod auth;
+pub mod batch;
+pub mod cart;
+pub mod checkout;
+pub mod order;
+pub mod realtime;
+pub mod record;
+pub mod superuser;
+pub mod user;
 pub mod validator;
\ No newline at end of file
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::models::order_status::OrderStatus;
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ChangeOrderStatus {
+    pub status: OrderStatus,
+    #[validate(length(max = 500))]
+    pub note: Option<String>,
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSubscriptions {
+    #[validate(length(min = 1))]
+    pub client_id: String,
+    /// Replaces the current subscriptions, an empty list unsubscribes from
+    /// everything.
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+}
@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use axum::extract::{FromRequest, Multipart, Request};
+use axum::http::header;
+use axum::Json;
+use serde_json::{Map, Value};
+
+use crate::errors::{BadRequest, Error};
+use crate::models::file::UploadedFile;
+use crate::models::record;
+
+/// Form field holding the JSON encoded values of a multipart request, as
+/// sent by the PocketBase SDKs.
+const JSON_PAYLOAD_FIELD: &str = "@jsonPayload";
+
+/// Record fields sent as JSON, or as `multipart/form-data` along with
+/// uploaded files.
+#[derive(Debug, Default)]
+pub struct RecordData {
+    pub data: Value,
+    /// Fields sent as multipart text, whose string values are converted to
+    /// the type of their column.
+    pub text_fields: Vec<String>,
+    pub files: Vec<UploadedFile>,
+}
+
+impl From<Value> for RecordData {
+    fn from(data: Value) -> Self {
+        Self {
+            data,
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for RecordData
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let Json(data) = Json::<Value>::from_request(req, state).await?;
+            return Ok(data.into());
+        }
+
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|rejection| BadRequest::new(rejection.body_text()))?;
+        let mut data = Map::new();
+        let mut text_fields = Vec::new();
+        let mut files = Vec::new();
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|err| BadRequest::new(err.body_text()))?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            if let Some(filename) = field.file_name().map(str::to_string) {
+                let bytes = field.bytes().await.map_err(|err| BadRequest::new(err.body_text()))?;
+                // Browsers send empty file inputs as nameless empty files
+                if !(filename.is_empty() && bytes.is_empty()) {
+                    files.push(UploadedFile {
+                        field: name,
+                        filename,
+                        data: bytes,
+                    });
+                }
+                continue;
+            }
+
+            let text = field.text().await.map_err(|err| BadRequest::new(err.body_text()))?;
+            if name == JSON_PAYLOAD_FIELD {
+                let Ok(Value::Object(payload)) = serde_json::from_str(&text) else {
+                    return Err(record::invalid_formatting());
+                };
+                data.extend(payload);
+                continue;
+            }
+
+            // Repeated fields, e.g. several `images-`, are collected in a list
+            match data.get_mut(&name) {
+                Some(Value::Array(values)) => values.push(Value::String(text)),
+                Some(value) => *value = Value::Array(vec![value.take(), Value::String(text)]),
+                None => {
+                    data.insert(name.clone(), Value::String(text));
+                    text_fields.push(name);
+                }
+            }
+        }
+
+        Ok(Self {
+            data: Value::Object(data),
+            text_fields,
+            files,
+        })
+    }
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct Impersonate {
+    /// Token lifetime in seconds.
+    #[validate(range(min = 1, max = 3600))]
+    pub duration: Option<i64>,
+}
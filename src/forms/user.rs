@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUser {
+    /// Generated when omitted.
+    pub id: Option<String>,
+    #[validate(email)]
+    pub email: String,
+    #[validate(length(min = 1))]
+    pub username: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub email_visibility: bool,
+    pub password: String,
+    pub password_confirm: String,
+}
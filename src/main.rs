@@ -1,8 +1,11 @@
+use clap::Parser;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use tokio::net::TcpListener;
-use tracing::info;
+use tracing::{info, warn};
 
 mod app;
+mod commands;
 mod controllers;
 mod errors;
 mod forms;
@@ -16,20 +19,88 @@ mod db {
     include!("../db/db.rs");
 }
 
-// Tests removed for now - can be added later as needed
-
+#[cfg(test)]
+mod tests;
 
+use commands::{Cli, Command};
+use models::fixture::OnConflict;
+use models::file::STORAGE;
+use models::password::{PASSWORDS, PASSWORD_POLICY};
+use models::storage;
+use once_cell::sync::Lazy;
 use settings::SETTINGS;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve { seed: false });
+
+    // Checked before anything reads `SETTINGS`, which panics on invalid
+    // configuration.
+    if let Command::CheckConfig = command {
+        commands::config::check()?;
+        return Ok(());
+    }
+
+    let level: tracing::Level = SETTINGS.logger.level.parse()?;
+    tracing_subscriber::fmt().with_max_level(level).init();
+
+    match command {
+        Command::Serve { seed } => serve(seed).await,
+        Command::Migrate { action } => {
+            let pools = db::connect(&SETTINGS.database).await?;
+            Ok(commands::migrate::run(&pools.primary, action).await?)
+        }
+        Command::Seed {
+            paths,
+            skip_existing,
+            force,
+        } => {
+            if SETTINGS.is_production() && !force {
+                return Err("Refusing to seed a production database, pass --force to seed anyway".into());
+            }
+            let pools = db::connect(&SETTINGS.database).await?;
+            db::migrate(&pools.primary, false).await?;
+            let on_conflict = if skip_existing { OnConflict::Skip } else { OnConflict::Update };
+            Ok(commands::seed::run(&pools.primary, &paths, on_conflict).await?)
+        }
+        Command::Superuser { action } => {
+            let pools = db::connect(&SETTINGS.database).await?;
+            Ok(commands::superuser::run(&pools.primary, action).await?)
+        }
+        Command::Backup => {
+            let pools = db::connect(&SETTINGS.database).await?;
+            let storage = storage::from_settings(&SETTINGS.storage)?;
+            commands::backup::run(&pools.primary, storage.as_ref()).await?;
+            Ok(())
+        }
+        Command::CheckConfig => unreachable!("handled before loading the settings"),
+    }
+}
+
+async fn serve(seed: bool) -> Result<(), Box<dyn std::error::Error>> {
     let port = SETTINGS.server.port;
     let address = SocketAddr::from(([127, 0, 0, 1], port));
 
-    let pool = db::create_pool().await?;
+    // Fail on startup rather than on the first login if the password settings
+    // or the breached password list are invalid, or on the first upload if
+    // the storage settings are.
+    Lazy::force(&PASSWORDS);
+    Lazy::force(&PASSWORD_POLICY);
+    Lazy::force(&STORAGE);
 
+    let pools = db::create_pool().await?;
+    if seed && SETTINGS.is_production() {
+        warn!("Not loading the sample data into a production database, use `seed --force` instead");
+    } else if seed {
+        // Records that fail to import are reported, the server starts anyway
+        let sample = [PathBuf::from(commands::seed::SAMPLE_DATA)];
+        if let Err(err) = commands::seed::run(&pools.primary, &sample, OnConflict::Skip).await {
+            warn!("Failed to load the sample data: {}", err);
+        }
+    }
 
-    let app = app::create_app(pool).await;
+    let app = app::create_app(pools.primary, pools.read).await;
 
     let listener = TcpListener::bind(address).await?;
     info!("Server listening on {}", &address);
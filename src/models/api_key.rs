@@ -0,0 +1,196 @@
+use chrono::{Duration, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::SqlitePool;
+use std::fmt::Write;
+
+use crate::errors::Error;
+use crate::models::pocketbase::{current_timestamp, generate_id, TIMESTAMP_FORMAT};
+
+const KEY_PREFIX: &str = "vsk_";
+const PREFIX_LENGTH: usize = 8;
+const SECRET_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiKeyScope {
+    #[serde(rename = "products:read")]
+    ProductsRead,
+    #[serde(rename = "products:write")]
+    ProductsWrite,
+    #[serde(rename = "orders:read")]
+    OrdersRead,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub store: String,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip)]
+    pub key_hash: String,
+    pub scopes: Json<Vec<ApiKeyScope>>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub created: String,
+    pub updated: String,
+}
+
+impl ApiKey {
+    /// Creates a new key for `store`. The plain text key is only returned
+    /// here, the record keeps a hash of it.
+    pub fn generate(store: String, name: String, scopes: Vec<ApiKeyScope>) -> (Self, String) {
+        let mut rng = rand::thread_rng();
+        let prefix = Alphanumeric
+            .sample_string(&mut rng, PREFIX_LENGTH)
+            .to_lowercase();
+        let secret = Alphanumeric.sample_string(&mut rng, SECRET_LENGTH);
+        let key = format!("{KEY_PREFIX}{prefix}_{secret}");
+
+        let now = current_timestamp();
+        let api_key = Self {
+            id: generate_id(),
+            store,
+            name,
+            prefix,
+            key_hash: hash_key(&key),
+            scopes: Json(scopes),
+            last_used_at: None,
+            revoked_at: None,
+            created: now.clone(),
+            updated: now,
+        };
+
+        (api_key, key)
+    }
+
+    /// Returns true if `token` looks like an API key rather than a JWT.
+    pub fn is_api_key(token: &str) -> bool {
+        token.starts_with(KEY_PREFIX)
+    }
+
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub async fn insert(&self, pool: &SqlitePool) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO api_keys (id, store, name, prefix, key_hash, scopes, created, updated) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&self.id)
+        .bind(&self.store)
+        .bind(&self.name)
+        .bind(&self.prefix)
+        .bind(&self.key_hash)
+        .bind(&self.scopes)
+        .bind(&self.created)
+        .bind(&self.updated)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_for_store(pool: &SqlitePool, store: &str) -> Result<Vec<Self>, Error> {
+        let keys = sqlx::query_as::<_, Self>(
+            "SELECT * FROM api_keys WHERE store = ? ORDER BY created DESC",
+        )
+        .bind(store)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// Revokes a key. Returns false if the store has no such active key.
+    pub async fn revoke(pool: &SqlitePool, store: &str, id: &str) -> Result<bool, Error> {
+        let now = current_timestamp();
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = ?, updated = ? \
+             WHERE id = ? AND store = ? AND revoked_at IS NULL",
+        )
+        .bind(&now)
+        .bind(&now)
+        .bind(id)
+        .bind(store)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Looks up an active key by id.
+    pub async fn find_active(pool: &SqlitePool, id: &str) -> Result<Option<Self>, Error> {
+        let api_key = sqlx::query_as::<_, Self>("SELECT * FROM api_keys WHERE id = ? AND revoked_at IS NULL")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(api_key)
+    }
+
+    /// Looks up the active key matching the plain text `key` and records its
+    /// use.
+    pub async fn authenticate(pool: &SqlitePool, key: &str) -> Result<Option<Self>, Error> {
+        let Some((prefix, _)) = key
+            .strip_prefix(KEY_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+        else {
+            return Ok(None);
+        };
+
+        let api_key = sqlx::query_as::<_, Self>(
+            "SELECT * FROM api_keys WHERE prefix = ? AND revoked_at IS NULL",
+        )
+        .bind(prefix)
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(mut api_key) = api_key.filter(|api_key| constant_time_eq(&api_key.key_hash, &hash_key(key)))
+        else {
+            return Ok(None);
+        };
+
+        // Keys used by sync jobs can be hit many times per second, only
+        // record their use once a minute.
+        let now = current_timestamp();
+        let threshold = (Utc::now() - Duration::minutes(1))
+            .format(TIMESTAMP_FORMAT)
+            .to_string();
+        let result = sqlx::query(
+            "UPDATE api_keys SET last_used_at = ? \
+             WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)",
+        )
+        .bind(&now)
+        .bind(&api_key.id)
+        .bind(&threshold)
+        .execute(pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            api_key.last_used_at = Some(now);
+        }
+
+        Ok(Some(api_key))
+    }
+}
+
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use crate::errors::Error;
+use crate::models::pocketbase::{current_timestamp, generate_id};
+
+pub const IMPERSONATE: &str = "impersonate";
+pub const IMPERSONATED_REQUEST: &str = "impersonated_request";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditLog {
+    pub id: String,
+    /// Superuser performing the action.
+    pub actor: String,
+    pub action: String,
+    /// Record the action applies to.
+    pub target: Option<String>,
+    pub details: Option<String>, // JSON as string
+    pub created: String,
+}
+
+impl AuditLog {
+    pub fn new(actor: &str, action: &str, target: Option<&str>, details: Value) -> Self {
+        Self {
+            id: generate_id(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            details: Some(details.to_string()),
+            created: current_timestamp(),
+        }
+    }
+
+    pub async fn insert(&self, pool: &SqlitePool) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO audit_logs (id, actor, action, target, details, created) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&self.id)
+        .bind(&self.actor)
+        .bind(&self.action)
+        .bind(&self.target)
+        .bind(&self.details)
+        .bind(&self.created)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
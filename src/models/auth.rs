@@ -31,6 +31,17 @@ pub struct Token {
     pub refresh_token: String,
 }
 
+pub const USERS_COLLECTION: &str = "users";
+pub const SUPERUSERS_COLLECTION: &str = "_superusers";
+pub const API_KEYS_COLLECTION: &str = "api_keys";
+
+/// Lifetime of a file token, long enough to start a download.
+const FILE_TOKEN_MINUTES: i64 = 2;
+
+fn default_collection() -> String {
+    USERS_COLLECTION.to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub authorized: bool,
@@ -38,6 +49,21 @@ pub struct Claims {
     pub refresh_uuid: Option<String>,
     pub user_id: String,
     pub exp: i64,
+    /// Collection `user_id` belongs to.
+    #[serde(default = "default_collection")]
+    pub collection: String,
+    /// Id of the superuser that minted this token to act as `user_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>,
+    /// File tokens only grant access to protected files.
+    #[serde(default)]
+    pub file: bool,
+}
+
+impl Claims {
+    pub fn is_superuser(&self) -> bool {
+        self.collection == SUPERUSERS_COLLECTION && self.impersonator.is_none()
+    }
 }
 
 pub struct AuthModel;
@@ -65,6 +91,9 @@ impl AuthModel {
             refresh_uuid: None,
             user_id: user_id.to_string(),
             exp: at_expires,
+            collection: default_collection(),
+            impersonator: None,
+            file: false,
         };
 
         let access_token = encode(
@@ -80,6 +109,9 @@ impl AuthModel {
             refresh_uuid: Some(refresh_uuid.clone()),
             user_id: user_id.to_string(),
             exp: rt_expires,
+            collection: default_collection(),
+            impersonator: None,
+            file: false,
         };
 
         let refresh_token = encode(
@@ -98,6 +130,86 @@ impl AuthModel {
         })
     }
 
+    /// Creates an access token for a superuser. Superuser sessions are not
+    /// refreshable.
+    pub fn create_superuser_token(&self, superuser_id: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let exp = (Utc::now() + Duration::minutes(15)).timestamp();
+        self.create_access_token(superuser_id, SUPERUSERS_COLLECTION, None, exp)
+    }
+
+    /// Creates a short-lived access token letting `impersonator` act as
+    /// `user_id`. No refresh token is issued so the session cannot outlive
+    /// `duration`.
+    pub fn create_impersonation_token(
+        &self,
+        user_id: &str,
+        impersonator: &str,
+        duration: Duration,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let exp = (Utc::now() + duration).timestamp();
+        self.create_access_token(user_id, USERS_COLLECTION, Some(impersonator.to_string()), exp)
+    }
+
+    fn create_access_token(
+        &self,
+        user_id: &str,
+        collection: &str,
+        impersonator: Option<String>,
+        exp: i64,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let access_secret = std::env::var("ACCESS_SECRET").unwrap_or_else(|_| "access-secret".to_string());
+
+        let claims = Claims {
+            authorized: true,
+            access_uuid: Some(Uuid::new_v4().to_string()),
+            refresh_uuid: None,
+            user_id: user_id.to_string(),
+            exp,
+            collection: collection.to_string(),
+            impersonator,
+            file: false,
+        };
+
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(access_secret.as_ref()),
+        )?;
+
+        Ok(token)
+    }
+
+    /// Creates a short-lived token to fetch protected files with the
+    /// credentials of `subject_id`, a user, superuser or API key of
+    /// `collection`. It cannot be used as an access token.
+    pub fn create_file_token(
+        &self,
+        subject_id: &str,
+        collection: &str,
+        impersonator: Option<String>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let access_secret = std::env::var("ACCESS_SECRET").unwrap_or_else(|_| "access-secret".to_string());
+
+        let claims = Claims {
+            authorized: false,
+            access_uuid: None,
+            refresh_uuid: None,
+            user_id: subject_id.to_string(),
+            exp: (Utc::now() + Duration::minutes(FILE_TOKEN_MINUTES)).timestamp(),
+            collection: collection.to_string(),
+            impersonator,
+            file: true,
+        };
+
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(access_secret.as_ref()),
+        )?;
+
+        Ok(token)
+    }
+
     pub fn extract_token(&self, auth_header: Option<&str>) -> Option<String> {
         if let Some(header) = auth_header {
             if header.starts_with("Bearer ") {
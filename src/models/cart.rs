@@ -0,0 +1,433 @@
+use rand::distributions::{Alphanumeric, DistString};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{Connection, SqliteConnection};
+
+use crate::errors::Error;
+use crate::models::checkout::{line_error, parse_amount};
+use crate::models::file::file_names;
+use crate::models::pocketbase::{current_timestamp, generate_id, Cart, CartItem};
+
+/// Cookie holding the session of a guest cart.
+pub const SESSION_COOKIE: &str = "vieshare_cart";
+/// Lifetime of the guest cart session cookie, in days.
+pub const SESSION_DAYS: i64 = 30;
+const SESSION_ID_LENGTH: usize = 32;
+
+/// Who the current cart belongs to: the authenticated user, or a guest
+/// identified by the session cookie.
+#[derive(Debug, Clone)]
+pub enum CartOwner {
+    User(String),
+    Session(String),
+}
+
+/// A cart with its items.
+#[derive(Debug, Serialize)]
+pub struct CartWithItems {
+    #[serde(flatten)]
+    pub cart: Cart,
+    pub items: Vec<CartItem>,
+}
+
+pub fn generate_session_id() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), SESSION_ID_LENGTH)
+}
+
+/// The current cart of `owner`, the most recently updated one if a user has
+/// several.
+pub async fn find(conn: &mut SqliteConnection, owner: &CartOwner) -> Result<Option<Cart>, Error> {
+    let query = match owner {
+        CartOwner::User(_) => "SELECT * FROM carts WHERE user = ? ORDER BY updated DESC, rowid DESC LIMIT 1",
+        CartOwner::Session(_) => {
+            "SELECT * FROM carts WHERE session_id = ? AND user IS NULL ORDER BY updated DESC, rowid DESC LIMIT 1"
+        }
+    };
+    let (CartOwner::User(key) | CartOwner::Session(key)) = owner;
+    let cart = sqlx::query_as::<_, Cart>(query)
+        .bind(key)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(cart)
+}
+
+/// The cart `id` if it belongs to `owner`. Other carts are not found.
+pub async fn find_owned(conn: &mut SqliteConnection, owner: &CartOwner, id: &str) -> Result<Cart, Error> {
+    let query = match owner {
+        CartOwner::User(_) => "SELECT * FROM carts WHERE id = ? AND user = ?",
+        CartOwner::Session(_) => "SELECT * FROM carts WHERE id = ? AND session_id = ? AND user IS NULL",
+    };
+    let (CartOwner::User(key) | CartOwner::Session(key)) = owner;
+    sqlx::query_as::<_, Cart>(query)
+        .bind(id)
+        .bind(key)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(Error::not_found)
+}
+
+async fn find_or_create(conn: &mut SqliteConnection, owner: &CartOwner) -> Result<Cart, Error> {
+    if let Some(cart) = find(conn, owner).await? {
+        return Ok(cart);
+    }
+
+    let cart = match owner {
+        CartOwner::User(user) => Cart::new(Some(user.clone()), None),
+        CartOwner::Session(session_id) => Cart::new(None, Some(session_id.clone())),
+    };
+    sqlx::query("INSERT INTO carts (id, user, session_id, created, updated) VALUES (?, ?, ?, ?, ?)")
+        .bind(&cart.id)
+        .bind(&cart.user)
+        .bind(&cart.session_id)
+        .bind(&cart.created)
+        .bind(&cart.updated)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(cart)
+}
+
+pub async fn items(conn: &mut SqliteConnection, cart_id: &str) -> Result<Vec<CartItem>, Error> {
+    let items = sqlx::query_as::<_, CartItem>("SELECT * FROM cart_items WHERE cart = ? ORDER BY rowid")
+        .bind(cart_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(items)
+}
+
+/// The current cart of `owner` with its items. Owners without a cart get
+/// not found.
+pub async fn view(conn: &mut SqliteConnection, owner: &CartOwner) -> Result<CartWithItems, Error> {
+    let cart = find(conn, owner).await?.ok_or_else(Error::not_found)?;
+    let items = items(conn, &cart.id).await?;
+
+    Ok(CartWithItems { cart, items })
+}
+
+/// Adds a product to the current cart of `owner`, created on the first
+/// item. A product already in the cart has its quantity increased.
+pub async fn add_item(
+    conn: &mut SqliteConnection,
+    owner: &CartOwner,
+    product: &str,
+    quantity: i64,
+) -> Result<CartItem, Error> {
+    let active: Option<bool> = sqlx::query_scalar("SELECT active FROM products WHERE id = ?")
+        .bind(product)
+        .fetch_optional(&mut *conn)
+        .await?;
+    match active {
+        None => {
+            return Err(Error::invalid_field(
+                "product",
+                "validation_missing_rel_records",
+                "Failed to find the product.",
+            ))
+        }
+        Some(false) => {
+            return Err(Error::invalid_field(
+                "product",
+                "validation_product_inactive",
+                "The product is no longer available.",
+            ))
+        }
+        Some(true) => {}
+    }
+
+    let mut tx = conn.begin().await?;
+    let cart = find_or_create(&mut tx, owner).await?;
+    let now = current_timestamp();
+    let existing: Option<String> = sqlx::query_scalar("SELECT id FROM cart_items WHERE cart = ? AND product = ? LIMIT 1")
+        .bind(&cart.id)
+        .bind(product)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let id = match existing {
+        Some(id) => {
+            sqlx::query("UPDATE cart_items SET quantity = quantity + ?, updated = ? WHERE id = ?")
+                .bind(quantity)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            id
+        }
+        None => {
+            let id = generate_id();
+            sqlx::query(
+                "INSERT INTO cart_items (id, cart, product, quantity, created, updated) VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(&cart.id)
+            .bind(product)
+            .bind(quantity)
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+            id
+        }
+    };
+    touch(&mut tx, &cart.id).await?;
+    let item = find_item(&mut tx, &cart.id, &id).await?;
+    tx.commit().await?;
+
+    Ok(item)
+}
+
+/// Sets the quantity of an item of the current cart of `owner`.
+pub async fn update_item(
+    conn: &mut SqliteConnection,
+    owner: &CartOwner,
+    id: &str,
+    quantity: i64,
+) -> Result<CartItem, Error> {
+    let mut tx = conn.begin().await?;
+    let cart = find(&mut tx, owner).await?.ok_or_else(Error::not_found)?;
+    let updated = sqlx::query("UPDATE cart_items SET quantity = ?, updated = ? WHERE id = ? AND cart = ?")
+        .bind(quantity)
+        .bind(current_timestamp())
+        .bind(id)
+        .bind(&cart.id)
+        .execute(&mut *tx)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Error::not_found());
+    }
+    touch(&mut tx, &cart.id).await?;
+    let item = find_item(&mut tx, &cart.id, id).await?;
+    tx.commit().await?;
+
+    Ok(item)
+}
+
+/// Removes an item from the current cart of `owner`.
+pub async fn remove_item(conn: &mut SqliteConnection, owner: &CartOwner, id: &str) -> Result<(), Error> {
+    let mut tx = conn.begin().await?;
+    let cart = find(&mut tx, owner).await?.ok_or_else(Error::not_found)?;
+    let deleted = sqlx::query("DELETE FROM cart_items WHERE id = ? AND cart = ?")
+        .bind(id)
+        .bind(&cart.id)
+        .execute(&mut *tx)
+        .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(Error::not_found());
+    }
+    touch(&mut tx, &cart.id).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Moves the guest cart of `session_id` to `user_id` after they sign in.
+/// Users without a cart take over the guest cart, otherwise its items are
+/// added to the user's current cart, summing the quantities of products in
+/// both, and the guest cart is deleted.
+pub async fn merge_guest_cart(conn: &mut SqliteConnection, session_id: &str, user_id: &str) -> Result<(), Error> {
+    let mut tx = conn.begin().await?;
+    let Some(guest) = find(&mut tx, &CartOwner::Session(session_id.to_string())).await? else {
+        return Ok(());
+    };
+
+    let now = current_timestamp();
+    let Some(cart) = find(&mut tx, &CartOwner::User(user_id.to_string())).await? else {
+        sqlx::query("UPDATE carts SET user = ?, session_id = NULL, updated = ? WHERE id = ?")
+            .bind(user_id)
+            .bind(&now)
+            .bind(&guest.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Ok(());
+    };
+
+    sqlx::query(
+        "UPDATE cart_items SET updated = ?, quantity = quantity + \
+         (SELECT SUM(guest.quantity) FROM cart_items AS guest WHERE guest.cart = ? AND guest.product = cart_items.product) \
+         WHERE cart = ? AND product IN (SELECT product FROM cart_items WHERE cart = ?)",
+    )
+    .bind(&now)
+    .bind(&guest.id)
+    .bind(&cart.id)
+    .bind(&guest.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE cart_items SET cart = ?, updated = ? \
+         WHERE cart = ? AND product NOT IN (SELECT product FROM cart_items WHERE cart = ?)",
+    )
+    .bind(&cart.id)
+    .bind(&now)
+    .bind(&guest.id)
+    .bind(&cart.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM cart_items WHERE cart = ?")
+        .bind(&guest.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM carts WHERE id = ?")
+        .bind(&guest.id)
+        .execute(&mut *tx)
+        .await?;
+    touch(&mut tx, &cart.id).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn find_item(conn: &mut SqliteConnection, cart_id: &str, id: &str) -> Result<CartItem, Error> {
+    sqlx::query_as::<_, CartItem>("SELECT * FROM cart_items WHERE id = ? AND cart = ?")
+        .bind(id)
+        .bind(cart_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(Error::not_found)
+}
+
+/// Marks the cart as the most recently updated one of its owner.
+async fn touch(conn: &mut SqliteConnection, cart_id: &str) -> Result<(), Error> {
+    sqlx::query("UPDATE carts SET updated = ? WHERE id = ?")
+        .bind(current_timestamp())
+        .bind(cart_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// A cart item with the current state of its product and store.
+#[derive(Debug, sqlx::FromRow)]
+struct SummaryLine {
+    id: String,
+    product: String,
+    quantity: i64,
+    name: String,
+    images: Option<String>,
+    price: String,
+    inventory: i64,
+    active: bool,
+    store: String,
+    store_name: String,
+}
+
+/// A cart item priced at the current price of its product.
+#[derive(Debug, Serialize)]
+pub struct SummaryItem {
+    pub id: String,
+    pub product: String,
+    pub name: String,
+    /// First image of the product, served under `/api/files/products/<product>/`.
+    pub image: Option<String>,
+    pub price: String,
+    pub quantity: i64,
+    pub subtotal: String,
+    pub store: String,
+    pub inventory: i64,
+    /// Why the item cannot be ordered as is, with the codes checkout fails
+    /// with.
+    pub warning: Option<Value>,
+}
+
+/// The items of a cart sold by one store.
+#[derive(Debug, Serialize)]
+pub struct StoreSubtotal {
+    pub store: String,
+    pub name: String,
+    /// Number of items.
+    pub count: usize,
+    pub quantity: i64,
+    pub subtotal: String,
+}
+
+/// The priced items of a cart, the subtotal of each store and the total,
+/// as checkout would charge them.
+#[derive(Debug, Serialize)]
+pub struct CartSummary {
+    pub cart: String,
+    pub items: Vec<SummaryItem>,
+    pub stores: Vec<StoreSubtotal>,
+    pub count: usize,
+    pub quantity: i64,
+    pub total: String,
+    /// Whether no item has a warning.
+    pub orderable: bool,
+}
+
+/// Prices the cart `id` of `owner`. Amounts are computed with exact decimal
+/// arithmetic, and stores are listed in the order they first appear in the
+/// cart.
+pub async fn summary(conn: &mut SqliteConnection, owner: &CartOwner, id: &str) -> Result<CartSummary, Error> {
+    let cart = find_owned(conn, owner, id).await?;
+    let lines = sqlx::query_as::<_, SummaryLine>(
+        "SELECT cart_items.id, cart_items.product, cart_items.quantity, products.name, products.images, \
+         products.price, products.inventory, products.active, products.store, stores.name AS store_name \
+         FROM cart_items JOIN products ON products.id = cart_items.product \
+         JOIN stores ON stores.id = products.store \
+         WHERE cart_items.cart = ? ORDER BY cart_items.rowid",
+    )
+    .bind(&cart.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut items = Vec::with_capacity(lines.len());
+    let mut stores: Vec<(StoreSubtotal, Decimal)> = Vec::new();
+    let mut total = Decimal::ZERO;
+    for line in lines {
+        let subtotal = parse_amount(&line.price)? * Decimal::from(line.quantity);
+        total += subtotal;
+        match stores.iter_mut().find(|(store, _)| store.store == line.store) {
+            Some((store, amount)) => {
+                store.count += 1;
+                store.quantity += line.quantity;
+                *amount += subtotal;
+            }
+            None => stores.push((
+                StoreSubtotal {
+                    store: line.store.clone(),
+                    name: line.store_name.clone(),
+                    count: 1,
+                    quantity: line.quantity,
+                    subtotal: String::new(),
+                },
+                subtotal,
+            )),
+        }
+
+        let image = line
+            .images
+            .map(|images| file_names(&Value::String(images)))
+            .and_then(|names| names.into_iter().next());
+        items.push(SummaryItem {
+            warning: line_error(&line.name, line.active, line.inventory, line.quantity),
+            id: line.id,
+            product: line.product,
+            name: line.name,
+            image,
+            price: line.price,
+            quantity: line.quantity,
+            subtotal: subtotal.to_string(),
+            store: line.store,
+            inventory: line.inventory,
+        });
+    }
+
+    Ok(CartSummary {
+        cart: cart.id,
+        count: items.len(),
+        quantity: items.iter().map(|item| item.quantity).sum(),
+        orderable: items.iter().all(|item| item.warning.is_none()),
+        items,
+        stores: stores
+            .into_iter()
+            .map(|(store, amount)| StoreSubtotal {
+                subtotal: amount.to_string(),
+                ..store
+            })
+            .collect(),
+        total: total.to_string(),
+    })
+}
@@ -0,0 +1,289 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{Connection, SqliteConnection};
+use std::str::FromStr;
+
+use crate::errors::{BadRequest, Error};
+use crate::forms::checkout::Checkout;
+use crate::models::pocketbase::{current_timestamp, generate_id, Cart, Order, User};
+use crate::models::record::{self, PendingFiles};
+use crate::utils::auth::Auth;
+
+/// A cart item with the current state of its product.
+#[derive(Debug, sqlx::FromRow)]
+struct CartLine {
+    id: String,
+    product: String,
+    quantity: i64,
+    name: String,
+    price: String,
+    inventory: i64,
+    active: bool,
+    store: String,
+}
+
+/// A line of `orders.items`: the product as it was sold, so later price or
+/// name changes do not alter the order.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderItem {
+    pub product: String,
+    pub name: String,
+    pub price: String,
+    pub quantity: i64,
+    pub subtotal: String,
+}
+
+/// Parses a price or an amount stored as text.
+pub fn parse_amount(value: &str) -> Result<Decimal, Error> {
+    Decimal::from_str(value.trim())
+        .map_err(|err| anyhow::anyhow!("Invalid amount {value:?}: {err}").into())
+}
+
+/// Finds the cart `id` of `user`. Carts of other users are not found.
+async fn find_cart(conn: &mut SqliteConnection, user_id: &str, id: &str) -> Result<Cart, Error> {
+    sqlx::query_as::<_, Cart>("SELECT * FROM carts WHERE id = ? AND user = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(Error::not_found)
+}
+
+/// The orders placed by a checkout, one per store of the cart, with the
+/// totals the buyer is charged.
+#[derive(Debug, Serialize)]
+pub struct PlacedOrders {
+    /// Group id, held by the `checkout` field of the orders.
+    pub id: String,
+    pub amount: String,
+    pub quantity: i64,
+    pub orders: Vec<Order>,
+}
+
+/// Turns the cart `cart_id` of the authenticated user into orders, in a
+/// single transaction: the products must be active and in stock, their
+/// inventory is decremented, the items of each store are copied into an
+/// order of that store with their current price, the buyer is counted as a
+/// customer of the stores and the cart is emptied.
+pub async fn checkout(
+    conn: &mut SqliteConnection,
+    auth: &Auth,
+    cart_id: &str,
+    form: Checkout,
+) -> Result<PlacedOrders, Error> {
+    let user_id = auth.user().map(|user| user.id.clone()).ok_or_else(Error::forbidden)?;
+
+    let mut tx = conn.begin().await?;
+    let cart = find_cart(&mut tx, &user_id, cart_id).await?;
+    let user = record::find::<User>(&mut tx, &user_id).await?.ok_or_else(Error::not_found)?;
+    let owns_address: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM addresses WHERE id = ? AND user = ?)")
+        .bind(&form.address)
+        .bind(&user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !owns_address {
+        return Err(Error::invalid_field(
+            "address",
+            "validation_missing_rel_records",
+            "Failed to find the address.",
+        ));
+    }
+
+    let lines = sqlx::query_as::<_, CartLine>(
+        "SELECT cart_items.id, cart_items.product, cart_items.quantity, products.name, products.price, \
+         products.inventory, products.active, products.store \
+         FROM cart_items JOIN products ON products.id = cart_items.product \
+         WHERE cart_items.cart = ? ORDER BY cart_items.rowid",
+    )
+    .bind(&cart.id)
+    .fetch_all(&mut *tx)
+    .await?;
+    if lines.is_empty() {
+        return Err(BadRequest::new("The cart is empty.").into());
+    }
+    check_lines(&lines)?;
+
+    for line in &lines {
+        // Checked again by the update, as the stock may have changed since
+        // the cart was read
+        let updated = sqlx::query(
+            "UPDATE products SET inventory = inventory - ?, updated = ? WHERE id = ? AND inventory >= ?",
+        )
+        .bind(line.quantity)
+        .bind(current_timestamp())
+        .bind(&line.product)
+        .bind(line.quantity)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(out_of_stock(line));
+        }
+    }
+
+    let name = form.name.or_else(|| user.name.clone()).unwrap_or_else(|| user.username.clone());
+    let email = form.email.unwrap_or_else(|| user.email.clone());
+    let mut placed = PlacedOrders {
+        id: generate_id(),
+        amount: String::new(),
+        quantity: 0,
+        orders: Vec::new(),
+    };
+    let mut total = Decimal::ZERO;
+    let mut files = PendingFiles::default();
+    for (store, lines) in by_store(&lines) {
+        let mut items = Vec::with_capacity(lines.len());
+        let mut amount = Decimal::ZERO;
+        let mut quantity = 0;
+        for line in lines {
+            let subtotal = parse_amount(&line.price)? * Decimal::from(line.quantity);
+            amount += subtotal;
+            quantity += line.quantity;
+            items.push(OrderItem {
+                product: line.product.clone(),
+                name: line.name.clone(),
+                price: line.price.clone(),
+                quantity: line.quantity,
+                subtotal: subtotal.to_string(),
+            });
+        }
+
+        let data = json!({
+            "user": user_id,
+            "store": store,
+            "items": serde_json::to_string(&items).map_err(anyhow::Error::from)?,
+            "quantity": quantity,
+            "amount": amount.to_string(),
+            "name": name,
+            "email": email,
+            "address": form.address,
+            "notes": form.notes,
+            "checkout": placed.id,
+        });
+        let order = record::create_trusted_in::<Order>(&mut tx, data, &mut files).await?;
+        add_customer(&mut tx, &user, &order, amount).await?;
+        total += amount;
+        placed.quantity += quantity;
+        placed.orders.push(order);
+    }
+    placed.amount = total.to_string();
+
+    sqlx::query("DELETE FROM cart_items WHERE cart = ?")
+        .bind(&cart.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(placed)
+}
+
+/// Groups cart lines by store, in the order the stores first appear in the
+/// cart.
+fn by_store(lines: &[CartLine]) -> Vec<(&str, Vec<&CartLine>)> {
+    let mut groups: Vec<(&str, Vec<&CartLine>)> = Vec::new();
+    for line in lines {
+        match groups.iter_mut().find(|(store, _)| *store == line.store) {
+            Some((_, group)) => group.push(line),
+            None => groups.push((&line.store, vec![line])),
+        }
+    }
+
+    groups
+}
+
+/// Fails with the items whose product is inactive or lacks stock, keyed by
+/// cart item id.
+fn check_lines(lines: &[CartLine]) -> Result<(), Error> {
+    let mut items = Map::new();
+    for line in lines {
+        if let Some(error) = line_error(&line.name, line.active, line.inventory, line.quantity) {
+            items.insert(line.id.clone(), error);
+        }
+    }
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    Err(unavailable(items))
+}
+
+fn out_of_stock(line: &CartLine) -> Error {
+    let mut items = Map::new();
+    items.insert(line.id.clone(), stock_error(&line.name, line.inventory));
+    unavailable(items)
+}
+
+fn stock_error(name: &str, inventory: i64) -> Value {
+    json!({
+        "code": "validation_out_of_stock",
+        "message": format!("Only {} of {name} left in stock.", inventory.max(0)),
+    })
+}
+
+/// Why `quantity` of a product cannot be ordered, if it cannot: the product
+/// is inactive or lacks stock.
+pub fn line_error(name: &str, active: bool, inventory: i64, quantity: i64) -> Option<Value> {
+    if !active {
+        return Some(json!({ "code": "validation_product_inactive", "message": format!("{name} is no longer available.") }));
+    }
+    if inventory < quantity {
+        return Some(stock_error(name, inventory));
+    }
+
+    None
+}
+
+fn unavailable(items: Map<String, Value>) -> Error {
+    let mut data = Map::new();
+    data.insert("items".to_string(), Value::Object(items));
+    Error::BadRequest(BadRequest {
+        message: "Some products cannot be ordered.".to_string(),
+        data,
+    })
+}
+
+/// Counts the order in the totals of the buyer's `customers` row of the
+/// store, created on their first order. Customers are identified by the
+/// buyer's account and have its email: the name entered at checkout only
+/// fills in a new row, it never changes an existing one.
+async fn add_customer(conn: &mut SqliteConnection, user: &User, order: &Order, amount: Decimal) -> Result<(), Error> {
+    let existing: Option<(String, Option<String>)> =
+        sqlx::query_as("SELECT id, total_spent FROM customers WHERE store = ? AND user = ? LIMIT 1")
+            .bind(&order.store)
+            .bind(&user.id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    let now = current_timestamp();
+    match existing {
+        Some((id, total_spent)) => {
+            let total_spent = parse_amount(total_spent.as_deref().unwrap_or("0"))? + amount;
+            sqlx::query(
+                "UPDATE customers SET total_orders = total_orders + 1, total_spent = ?, updated = ? WHERE id = ?",
+            )
+            .bind(total_spent.to_string())
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {
+            sqlx::query(
+                "INSERT INTO customers (id, name, email, store, user, total_orders, total_spent, created, updated) \
+                 VALUES (?, ?, ?, ?, ?, 1, ?, ?, ?)",
+            )
+            .bind(generate_id())
+            .bind(&order.name)
+            .bind(&user.email)
+            .bind(&order.store)
+            .bind(&user.id)
+            .bind(amount.to_string())
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,478 @@
+use axum::extract::Request;
+use axum::response::Response;
+use bytes::Bytes;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+use sqlx::SqliteConnection;
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+use tracing::warn;
+
+use crate::errors::Error;
+use crate::models::pocketbase::generate_id;
+use crate::models::record::{self, Record};
+use crate::models::storage::{self, Storage};
+use crate::settings::SETTINGS;
+use crate::utils::auth::Auth;
+
+pub static STORAGE: Lazy<FileStorage> = Lazy::new(|| {
+    FileStorage::new(storage::from_settings(&SETTINGS.storage).expect("Failed to setup file storage"))
+});
+
+/// Types accepted by image fields, as detected from the file contents.
+pub const IMAGE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// Length the original name of an upload is truncated to.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Length of the random suffix making stored names unique.
+const SUFFIX_LENGTH: usize = 10;
+
+/// A column holding the names of uploaded files. Fields accepting several
+/// files store them as a JSON array.
+#[derive(Debug, Clone, Copy)]
+pub struct FileField {
+    pub name: &'static str,
+    pub max_select: usize,
+    /// Maximum size of each file, in bytes.
+    pub max_size: usize,
+    pub mime_types: &'static [&'static str],
+    /// Thumbnail sizes that can be requested, see `Thumb`.
+    pub thumbs: &'static [&'static str],
+    /// Protected files are only served with a file token of someone who may
+    /// view the record.
+    pub protected: bool,
+}
+
+impl FileField {
+    fn accepts(&self, field: &str) -> bool {
+        field.strip_suffix('+').unwrap_or(field) == self.name
+    }
+}
+
+/// Size of the largest set of uploads a single record accepts, given the
+/// file fields of each collection.
+pub const fn max_upload_size(collections: &[&[FileField]]) -> usize {
+    let mut largest = 0;
+    let mut i = 0;
+    while i < collections.len() {
+        let fields = collections[i];
+        let mut size = 0;
+        let mut j = 0;
+        while j < fields.len() {
+            size += fields[j].max_select * fields[j].max_size;
+            j += 1;
+        }
+        if size > largest {
+            largest = size;
+        }
+        i += 1;
+    }
+    largest
+}
+
+/// A file sent with `multipart/form-data`.
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+    /// Form field the file was sent as, e.g. `images` or `images+`.
+    pub field: String,
+    pub filename: String,
+    pub data: Bytes,
+}
+
+/// Files to write and to delete once a record is saved.
+#[derive(Debug, Default)]
+pub struct FileChanges {
+    uploads: Vec<(String, Bytes)>,
+    pub removed: Vec<String>,
+}
+
+/// Applies the file fields of client `data` to the `existing` record, or to
+/// a new one, and replaces them with the resulting file names.
+///
+/// Uploads sent as `field` or `field+` are validated and stored under a
+/// unique name, added to the current files or, for single file fields,
+/// replacing them. `field-` lists names to remove. A value sent for the field
+/// itself can only keep or reorder files the record already has.
+pub fn apply(
+    fields: &[FileField],
+    data: &mut Map<String, Value>,
+    existing: Option<&Map<String, Value>>,
+    files: Vec<UploadedFile>,
+) -> Result<FileChanges, Error> {
+    if let Some(file) = files.iter().find(|file| !fields.iter().any(|field| field.accepts(&file.field))) {
+        return Err(Error::invalid_field(
+            &file.field,
+            "validation_invalid_file",
+            "The field does not accept files.",
+        ));
+    }
+
+    let mut changes = FileChanges::default();
+    for field in fields {
+        let uploads: Vec<&UploadedFile> = files.iter().filter(|file| field.accepts(&file.field)).collect();
+        let replaced = data.remove(field.name);
+        let removed = data.remove(&format!("{}-", field.name));
+        // Only files can be appended
+        data.remove(&format!("{}+", field.name));
+        if replaced.is_none() && removed.is_none() && uploads.is_empty() {
+            continue;
+        }
+
+        let current = existing
+            .and_then(|existing| existing.get(field.name))
+            .map(file_names)
+            .unwrap_or_default();
+        let mut names: Vec<String> = match replaced {
+            Some(value) => file_names(&value)
+                .into_iter()
+                .filter(|name| current.contains(name))
+                .collect(),
+            None => current.clone(),
+        };
+        if let Some(removed) = removed {
+            let removed = file_names(&removed);
+            names.retain(|name| !removed.contains(name));
+        }
+        if field.max_select == 1 && !uploads.is_empty() {
+            names.clear();
+        }
+        for upload in uploads {
+            let name = stored_name(field, upload)?;
+            names.push(name.clone());
+            changes.uploads.push((name, upload.data.clone()));
+        }
+        if names.len() > field.max_select {
+            return Err(Error::invalid_field(
+                field.name,
+                "validation_max_select_limit",
+                &format!("Select no more than {} files.", field.max_select),
+            ));
+        }
+
+        changes
+            .removed
+            .extend(current.into_iter().filter(|name| !names.contains(name)));
+        let value = if field.max_select == 1 {
+            names.pop().map(Value::String).unwrap_or(Value::Null)
+        } else {
+            Value::String(json!(names).to_string())
+        };
+        data.insert(field.name.to_string(), value);
+    }
+
+    Ok(changes)
+}
+
+/// Finds the field of the `T` record `record_id` holding the file `name`.
+/// Protected files also require `auth` to satisfy the view rule.
+pub async fn find_field<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    record_id: &str,
+    name: &str,
+) -> Result<&'static FileField, Error> {
+    let record = record::find::<T>(conn, record_id).await?.ok_or_else(Error::not_found)?;
+    let record = serde_json::to_value(record).map_err(anyhow::Error::from)?;
+
+    let field = T::FILE_FIELDS
+        .iter()
+        .find(|field| file_names(&record[field.name]).iter().any(|file| file == name))
+        .ok_or_else(Error::not_found)?;
+    if field.protected {
+        match record::view::<T>(conn, auth, record_id).await {
+            Ok(_) => {}
+            Err(Error::NotFound(_) | Error::Forbidden(_)) => return Err(Error::forbidden()),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(field)
+}
+
+/// Names held by a file field, a single name or a JSON array of them.
+pub fn file_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(names) if names.trim_start().starts_with('[') => {
+            serde_json::from_str(names).unwrap_or_default()
+        }
+        Value::String(name) if !name.is_empty() => vec![name.clone()],
+        Value::Array(names) => names.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Checks an upload against its field and returns the name it is stored
+/// under, e.g. `blue_shirt_k8w2qn0xz3.png`.
+fn stored_name(field: &FileField, file: &UploadedFile) -> Result<String, Error> {
+    if file.data.len() > field.max_size {
+        return Err(Error::invalid_field(
+            field.name,
+            "validation_file_size_limit",
+            &format!(
+                "Failed to upload {:?} - the maximum allowed file size is {} bytes.",
+                file.filename, field.max_size
+            ),
+        ));
+    }
+
+    // The declared content type is not trusted
+    let kind = infer::get(&file.data);
+    let mime_type = kind.map_or("application/octet-stream", |kind| kind.mime_type());
+    if !field.mime_types.contains(&mime_type) {
+        return Err(Error::invalid_field(
+            field.name,
+            "validation_invalid_mime_type",
+            &format!(
+                "{:?} mime type must be one of: {}.",
+                file.filename,
+                field.mime_types.join(", ")
+            ),
+        ));
+    }
+
+    let stem = Path::new(&file.filename)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let mut name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    name = name.trim_matches('_').to_string();
+    if name.is_empty() {
+        name = "file".to_string();
+    }
+    let suffix = &generate_id()[..SUFFIX_LENGTH];
+    let extension = kind.map_or("bin", |kind| kind.extension());
+
+    Ok(format!("{name}_{suffix}.{extension}"))
+}
+
+/// Whether `name` can be used as a path component without leaving its
+/// directory.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Crop {
+    Center,
+    Top,
+    Bottom,
+    /// Resized to fit, without cropping.
+    Fit,
+}
+
+/// A thumbnail size, in the PocketBase format:
+///
+/// - `WxH` crops to the size from the center, `WxHt` from the top and `WxHb`
+///   from the bottom
+/// - `WxHf` fits the image inside the size, without cropping
+/// - `0xH` and `Wx0` resize to the height or width, keeping the aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thumb {
+    width: u32,
+    height: u32,
+    crop: Crop,
+}
+
+impl Thumb {
+    pub fn parse(thumb: &str) -> Option<Self> {
+        let (size, crop) = match thumb.as_bytes().last()? {
+            b't' => (&thumb[..thumb.len() - 1], Crop::Top),
+            b'b' => (&thumb[..thumb.len() - 1], Crop::Bottom),
+            b'f' => (&thumb[..thumb.len() - 1], Crop::Fit),
+            _ => (thumb, Crop::Center),
+        };
+        let (width, height) = size.split_once('x')?;
+        let thumb = Self {
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+            crop,
+        };
+        let resize_only = thumb.width == 0 || thumb.height == 0;
+        if (thumb.width == 0 && thumb.height == 0) || (resize_only && crop != Crop::Center) {
+            return None;
+        }
+
+        Some(thumb)
+    }
+
+    fn resize(&self, image: &DynamicImage) -> DynamicImage {
+        let (width, height) = (self.width, self.height);
+        if width == 0 {
+            return image.resize(u32::MAX, height, FilterType::Lanczos3);
+        }
+        if height == 0 {
+            return image.resize(width, u32::MAX, FilterType::Lanczos3);
+        }
+
+        match self.crop {
+            Crop::Fit => image.resize(width, height, FilterType::Lanczos3),
+            Crop::Center => image.resize_to_fill(width, height, FilterType::Lanczos3),
+            Crop::Top | Crop::Bottom => {
+                // Scaled to cover the size, then cropped vertically
+                let scale = f64::max(
+                    f64::from(width) / f64::from(image.width()),
+                    f64::from(height) / f64::from(image.height()),
+                );
+                let scaled_width = (f64::from(image.width()) * scale).ceil() as u32;
+                let scaled_height = (f64::from(image.height()) * scale).ceil() as u32;
+                let scaled = image.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3);
+                let x = scaled_width.saturating_sub(width) / 2;
+                let y = match self.crop {
+                    Crop::Top => 0,
+                    _ => scaled_height.saturating_sub(height),
+                };
+                scaled.crop_imm(x, y, width, height)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Thumb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let crop = match self.crop {
+            Crop::Center => "",
+            Crop::Top => "t",
+            Crop::Bottom => "b",
+            Crop::Fit => "f",
+        };
+        write!(f, "{}x{}{}", self.width, self.height, crop)
+    }
+}
+
+/// Decodes an image, resizes it and encodes it back in `format`.
+fn generate_thumb(data: &[u8], format: ImageFormat, thumb: Thumb) -> anyhow::Result<Vec<u8>> {
+    let image = image::load_from_memory_with_format(data, format)?;
+    let mut resized = Cursor::new(Vec::new());
+    thumb.resize(&image).write_to(&mut resized, format)?;
+    Ok(resized.into_inner())
+}
+
+/// Uploaded files of records, stored as `<collection>/<record id>/<name>`
+/// and their thumbnails as `<collection>/<record id>/thumbs_<name>/<thumb>_<name>`.
+pub struct FileStorage {
+    storage: Box<dyn Storage>,
+}
+
+impl FileStorage {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    fn record_prefix(&self, collection: &str, record_id: &str) -> Option<String> {
+        (is_plain_name(collection) && is_plain_name(record_id)).then(|| format!("{collection}/{record_id}/"))
+    }
+
+    fn key(&self, collection: &str, record_id: &str, name: &str) -> Option<String> {
+        self.record_prefix(collection, record_id)
+            .filter(|_| is_plain_name(name))
+            .map(|prefix| format!("{prefix}{name}"))
+    }
+
+    fn thumbs_prefix(&self, collection: &str, record_id: &str, name: &str) -> Option<String> {
+        self.key(collection, record_id, name)
+            .map(|_| format!("{collection}/{record_id}/thumbs_{name}/"))
+    }
+
+    /// Responds to `request` with the file `name`, or one of its thumbnails.
+    pub async fn serve(
+        &self,
+        collection: &str,
+        record_id: &str,
+        name: &str,
+        thumb: Option<Thumb>,
+        request: Request,
+    ) -> Result<Response, Error> {
+        let key = match thumb {
+            Some(thumb) => self.thumbnail(collection, record_id, name, thumb).await?,
+            None => self.key(collection, record_id, name).ok_or_else(Error::not_found)?,
+        };
+        self.storage.serve(&key, request).await
+    }
+
+    /// Key of a thumbnail of the file `name`, generated on first use. Files
+    /// that are not supported images are returned as is.
+    async fn thumbnail(&self, collection: &str, record_id: &str, name: &str, thumb: Thumb) -> Result<String, Error> {
+        let source = self.key(collection, record_id, name).ok_or_else(Error::not_found)?;
+        let prefix = self.thumbs_prefix(collection, record_id, name).ok_or_else(Error::not_found)?;
+        let target = format!("{prefix}{thumb}_{name}");
+        if self.storage.exists(&target).await? {
+            return Ok(target);
+        }
+        let Ok(format) = ImageFormat::from_path(name) else {
+            return Ok(source);
+        };
+        let Some(data) = self.storage.read(&source).await? else {
+            return Err(Error::not_found());
+        };
+
+        let generated = tokio::task::spawn_blocking(move || generate_thumb(&data, format, thumb)).await?;
+        let written = match generated {
+            Ok(resized) => self.storage.write(&target, resized.into()).await,
+            Err(err) => Err(err.into()),
+        };
+        match written {
+            Ok(()) => Ok(target),
+            Err(err) => {
+                // Served in full rather than failing the request
+                warn!("Failed to generate the {} thumbnail of {}: {}", thumb, source, err);
+                Ok(source)
+            }
+        }
+    }
+
+    /// Writes the uploads of `changes`. Nothing is left behind on failure.
+    pub async fn save(&self, collection: &str, record_id: &str, changes: &FileChanges) -> Result<(), Error> {
+        for (name, data) in &changes.uploads {
+            let key = self.key(collection, record_id, name).ok_or(Error::InternalServerError)?;
+            if let Err(err) = self.storage.write(&key, data.clone()).await {
+                self.discard(collection, record_id, changes).await;
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the uploads of `changes`, once saving their record failed.
+    pub async fn discard(&self, collection: &str, record_id: &str, changes: &FileChanges) {
+        let names: Vec<String> = changes.uploads.iter().map(|(name, _)| name.clone()).collect();
+        self.remove(collection, record_id, &names).await;
+    }
+
+    /// Deletes files of a record. Failures are only logged, the record no
+    /// longer references the files.
+    pub async fn remove(&self, collection: &str, record_id: &str, names: &[String]) {
+        for name in names {
+            let (Some(key), Some(thumbs)) = (
+                self.key(collection, record_id, name),
+                self.thumbs_prefix(collection, record_id, name),
+            ) else {
+                continue;
+            };
+            if let Err(err) = self.storage.delete(&key).await {
+                warn!("Failed to delete {}: {}", key, err);
+            }
+            if let Err(err) = self.storage.delete_prefix(&thumbs).await {
+                warn!("Failed to delete {}: {}", thumbs, err);
+            }
+        }
+    }
+
+    /// Deletes all the files of a deleted record.
+    pub async fn remove_record(&self, collection: &str, record_id: &str) {
+        let Some(prefix) = self.record_prefix(collection, record_id) else {
+            return;
+        };
+        if let Err(err) = self.storage.delete_prefix(&prefix).await {
+            warn!("Failed to delete {}: {}", prefix, err);
+        }
+    }
+}
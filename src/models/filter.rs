@@ -0,0 +1,315 @@
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::errors::{BadRequest, Error};
+use crate::models::pocketbase::{current_timestamp, normalize_timestamp, TIMESTAMP_FORMAT};
+use crate::models::record::is_identifier;
+
+/// Nesting allowed in filters, to bound the recursion of the parser.
+const MAX_DEPTH: usize = 32;
+
+/// SQLite equivalent of `TIMESTAMP_FORMAT`, applied to datetime columns so
+/// values written before the format was normalized compare like the others.
+const SQL_TIMESTAMP_FORMAT: &str = "'%Y-%m-%d %H:%M:%fZ'";
+
+/// A PocketBase style `filter` query parameter converted to an SQL
+/// condition and the values bound to its placeholders.
+#[derive(Debug)]
+pub struct Filter {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Operator(&'static str),
+    Field(String),
+    Literal(Value),
+    Macro(String),
+}
+
+#[derive(Debug)]
+enum Operand {
+    Column { name: String, datetime: bool },
+    Literal(Value),
+}
+
+/// Parses expressions such as
+/// `(name ~ 'board' || price > 10) && created >= '2024-09-01'`.
+///
+/// `columns` maps the columns of the collection to their declared type.
+/// Datetime columns are compared in the stored timestamp format, and the
+/// dates and datetimes compared to them are converted to it, as are the
+/// `@now`, `@todayStart` and `@todayEnd` macros.
+pub fn parse(filter: &str, columns: &HashMap<String, String>) -> Result<Filter, Error> {
+    let tokens = tokenize(filter)?;
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+        columns,
+        params: Vec::new(),
+        depth: 0,
+    };
+    let sql = parser.or()?;
+    if parser.tokens.next().is_some() {
+        return Err(invalid_filter());
+    }
+
+    Ok(Filter {
+        sql,
+        params: parser.params,
+    })
+}
+
+fn invalid_filter() -> Error {
+    BadRequest::new("Invalid filter parameters.").into()
+}
+
+fn tokenize(filter: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                Token::Open
+            }
+            ')' => {
+                chars.next();
+                Token::Close
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    return Err(invalid_filter());
+                }
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            '=' | '!' | '>' | '<' | '~' => Token::Operator(operator(&mut chars)?),
+            '\'' | '"' => Token::Literal(Value::String(string(&mut chars)?)),
+            '@' => {
+                chars.next();
+                Token::Macro(word(&mut chars))
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => Token::Literal(number(&mut chars)?),
+            _ => {
+                let word = word(&mut chars);
+                match word.as_str() {
+                    "" => return Err(invalid_filter()),
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => Token::Field(word),
+                }
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn operator(chars: &mut Peekable<Chars>) -> Result<&'static str, Error> {
+    let first = chars.next();
+    let second = chars.peek().copied();
+    let operator = match (first, second) {
+        (Some('!'), Some('=')) => "!=",
+        (Some('!'), Some('~')) => "!~",
+        (Some('>'), Some('=')) => ">=",
+        (Some('<'), Some('=')) => "<=",
+        (Some('='), _) => return Ok("="),
+        (Some('>'), _) => return Ok(">"),
+        (Some('<'), _) => return Ok("<"),
+        (Some('~'), _) => return Ok("~"),
+        _ => return Err(invalid_filter()),
+    };
+    chars.next();
+
+    Ok(operator)
+}
+
+fn string(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    let quote = chars.next();
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => value.push(chars.next().ok_or_else(invalid_filter)?),
+            Some(c) if Some(c) == quote => return Ok(value),
+            Some(c) => value.push(c),
+            None => return Err(invalid_filter()),
+        }
+    }
+}
+
+fn number(chars: &mut Peekable<Chars>) -> Result<Value, Error> {
+    let mut number = String::new();
+    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || matches!(c, '-' | '.')) {
+        number.push(c);
+        chars.next();
+    }
+
+    if let Ok(integer) = number.parse::<i64>() {
+        return Ok(Value::from(integer));
+    }
+    number.parse::<f64>().map(Value::from).map_err(|_| invalid_filter())
+}
+
+fn word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+        word.push(c);
+        chars.next();
+    }
+
+    word
+}
+
+struct Parser<'a> {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    columns: &'a HashMap<String, String>,
+    params: Vec<Value>,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<String, Error> {
+        let mut sql = self.and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            sql = format!("{sql} OR {}", self.and()?);
+        }
+
+        Ok(sql)
+    }
+
+    fn and(&mut self) -> Result<String, Error> {
+        let mut sql = self.term()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            sql = format!("{sql} AND {}", self.term()?);
+        }
+
+        Ok(sql)
+    }
+
+    fn term(&mut self) -> Result<String, Error> {
+        if self.tokens.next_if_eq(&Token::Open).is_none() {
+            return self.comparison();
+        }
+
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid_filter());
+        }
+        let sql = self.or()?;
+        if self.tokens.next() != Some(Token::Close) {
+            return Err(invalid_filter());
+        }
+        self.depth -= 1;
+
+        Ok(format!("({sql})"))
+    }
+
+    fn comparison(&mut self) -> Result<String, Error> {
+        let left = self.operand()?;
+        let Some(Token::Operator(operator)) = self.tokens.next() else {
+            return Err(invalid_filter());
+        };
+        let right = self.operand()?;
+
+        let datetime = [&left, &right]
+            .iter()
+            .any(|operand| matches!(operand, Operand::Column { datetime: true, .. }));
+        let left = self.sql(left, operator, datetime)?;
+        let right = self.sql(right, operator, datetime)?;
+
+        let sql = match (operator, left.as_str(), right.as_str()) {
+            ("=", _, "NULL") => format!("{left} IS NULL"),
+            ("=", "NULL", _) => format!("{right} IS NULL"),
+            ("!=", _, "NULL") => format!("{left} IS NOT NULL"),
+            ("!=", "NULL", _) => format!("{right} IS NOT NULL"),
+            (_, "NULL", _) | (_, _, "NULL") => return Err(invalid_filter()),
+            ("=", ..) => format!("{left} = {right}"),
+            ("!=", ..) => format!("{left} IS NOT {right}"),
+            ("~", ..) => format!("{left} LIKE {right} ESCAPE '\\'"),
+            ("!~", ..) => format!("{left} NOT LIKE {right} ESCAPE '\\'"),
+            (operator, ..) => format!("{left} {operator} {right}"),
+        };
+
+        Ok(sql)
+    }
+
+    fn operand(&mut self) -> Result<Operand, Error> {
+        let operand = match self.tokens.next() {
+            Some(Token::Field(name)) => {
+                if !is_identifier(&name) {
+                    return Err(invalid_filter());
+                }
+                let column_type = self.columns.get(&name).ok_or_else(invalid_filter)?;
+                Operand::Column {
+                    datetime: column_type.eq_ignore_ascii_case("DATETIME"),
+                    name,
+                }
+            }
+            Some(Token::Literal(value)) => Operand::Literal(value),
+            Some(Token::Macro(name)) => {
+                let today = Utc::now()
+                    .date_naive()
+                    .and_hms_opt(0, 0, 0)
+                    .ok_or_else(invalid_filter)?
+                    .and_utc();
+                let value = match name.as_str() {
+                    "now" => current_timestamp(),
+                    "todayStart" => today.format(TIMESTAMP_FORMAT).to_string(),
+                    "todayEnd" => (today + Duration::days(1) - Duration::milliseconds(1))
+                        .format(TIMESTAMP_FORMAT)
+                        .to_string(),
+                    _ => return Err(invalid_filter()),
+                };
+                Operand::Literal(Value::String(value))
+            }
+            _ => return Err(invalid_filter()),
+        };
+
+        Ok(operand)
+    }
+
+    /// SQL for one side of a comparison, with literals bound as parameters.
+    fn sql(&mut self, operand: Operand, operator: &str, datetime: bool) -> Result<String, Error> {
+        let value = match operand {
+            Operand::Column { name, .. } if datetime => {
+                return Ok(format!("strftime({SQL_TIMESTAMP_FORMAT}, \"{name}\")"));
+            }
+            Operand::Column { name, .. } => return Ok(format!("\"{name}\"")),
+            Operand::Literal(Value::Null) => return Ok("NULL".to_string()),
+            Operand::Literal(value) => value,
+        };
+
+        let value = match value {
+            Value::String(value) if operator.ends_with('~') => {
+                let escaped = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+                Value::String(format!("%{escaped}%"))
+            }
+            Value::String(value) if datetime => {
+                Value::String(normalize_timestamp(&value).ok_or_else(invalid_filter)?)
+            }
+            value => value,
+        };
+        self.params.push(value);
+
+        Ok("?".to_string())
+    }
+}
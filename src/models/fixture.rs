@@ -0,0 +1,355 @@
+use anyhow::{bail, Context};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use validator::Validate;
+
+use crate::errors::{is_foreign_key_violation, Error};
+use crate::models::pocketbase::{
+    current_timestamp, normalize_timestamp, Address, Cart, CartItem, Category, Customer, Notification, Order, Product,
+    Store, Subcategory, User,
+};
+use crate::models::record::{bind_value, is_identifier, missing_relations, text_value};
+use crate::models::user::hash_password;
+
+/// Collections that can be imported, parents before the collections
+/// referencing them.
+pub const COLLECTIONS: [&str; 11] = [
+    "users",
+    "categories",
+    "subcategories",
+    "stores",
+    "products",
+    "carts",
+    "cart_items",
+    "addresses",
+    "orders",
+    "customers",
+    "notifications",
+];
+
+/// What to do with records whose id already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Leave the existing record untouched.
+    Skip,
+    /// Overwrite the fields present in the dataset.
+    Update,
+}
+
+/// Records to import, grouped by collection.
+#[derive(Debug, Default)]
+pub struct Dataset {
+    records: HashMap<String, Vec<Map<String, Value>>>,
+}
+
+impl Dataset {
+    /// Adds a `.json` document (`{"collection": [records]}`), a
+    /// `<collection>.csv` file or a directory containing such files. CSV
+    /// columns are read from the schema, so `pool` must be migrated.
+    pub async fn add(&mut self, pool: &SqlitePool, path: &Path) -> anyhow::Result<()> {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.sort();
+            for entry in entries.iter().filter(|entry| entry.is_file()) {
+                match entry.extension().and_then(|extension| extension.to_str()) {
+                    Some("json") | Some("csv") => Box::pin(self.add(pool, entry)).await?,
+                    _ => {}
+                }
+            }
+            return Ok(());
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => self.add_json(path),
+            Some("csv") => self.add_csv(pool, path).await,
+            _ => bail!("Unsupported dataset {}, expected a .json or .csv file", path.display()),
+        }
+    }
+
+    fn add_json(&mut self, path: &Path) -> anyhow::Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let document: Map<String, Value> = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        for (collection, records) in document {
+            let Value::Array(records) = records else {
+                bail!("Expected an array of {collection} records in {}", path.display());
+            };
+            let records = records
+                .into_iter()
+                .map(|record| match record {
+                    Value::Object(record) => Ok(record),
+                    _ => bail!("Expected {collection} records to be objects in {}", path.display()),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            self.extend(&collection, records)?;
+        }
+
+        Ok(())
+    }
+
+    /// CSV cells are converted according to the declared type of their
+    /// column, see `text_value`.
+    async fn add_csv(&mut self, pool: &SqlitePool, path: &Path) -> anyhow::Result<()> {
+        let Some(collection) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            bail!("Invalid file name {}", path.display());
+        };
+        check_collection(collection)?;
+
+        let column_types: HashMap<String, String> =
+            sqlx::query_as("SELECT name, upper(type) FROM pragma_table_info(?)")
+                .bind(collection)
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let headers = reader.headers()?.clone();
+        let mut records = Vec::new();
+        for row in reader.records() {
+            let row = row.with_context(|| format!("Failed to parse {}", path.display()))?;
+            let record = headers
+                .iter()
+                .zip(row.iter())
+                .map(|(column, cell)| {
+                    let column_type = column_types.get(column).map(String::as_str).unwrap_or("TEXT");
+                    (column.to_string(), text_value(cell, column_type))
+                })
+                .collect();
+            records.push(record);
+        }
+
+        self.extend(collection, records)
+    }
+
+    fn extend(&mut self, collection: &str, records: Vec<Map<String, Value>>) -> anyhow::Result<()> {
+        check_collection(collection)?;
+        self.records.entry(collection.to_string()).or_default().extend(records);
+        Ok(())
+    }
+}
+
+fn check_collection(collection: &str) -> anyhow::Result<()> {
+    if !COLLECTIONS.contains(&collection) {
+        bail!("Unknown collection {collection:?}");
+    }
+    Ok(())
+}
+
+/// A record that could not be imported.
+#[derive(Debug)]
+pub struct RowError {
+    pub collection: String,
+    /// 1-based position of the record in its collection.
+    pub row: usize,
+    pub id: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} row {}", self.collection, self.row)?;
+        if let Some(id) = &self.id {
+            write!(f, " ({id})")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Number of records written per collection, in import order.
+    pub imported: Vec<(&'static str, usize)>,
+    pub skipped: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Imports `dataset` in dependency order. Each record is validated against
+/// its model and upserted by id; invalid records are reported without
+/// stopping the import.
+pub async fn import(pool: &SqlitePool, dataset: &Dataset, on_conflict: OnConflict) -> anyhow::Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut tx = pool.begin().await?;
+
+    for collection in COLLECTIONS {
+        let Some(records) = dataset.records.get(collection) else {
+            continue;
+        };
+
+        let mut imported = 0;
+        for (index, record) in records.iter().enumerate() {
+            match import_record(&mut tx, collection, record.clone(), on_conflict).await {
+                Ok(true) => imported += 1,
+                Ok(false) => report.skipped += 1,
+                Err(message) => report.errors.push(RowError {
+                    collection: collection.to_string(),
+                    row: index + 1,
+                    id: record.get("id").and_then(Value::as_str).map(str::to_string),
+                    message,
+                }),
+            }
+        }
+        report.imported.push((collection, imported));
+    }
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// Returns whether the record was written.
+async fn import_record(
+    conn: &mut SqliteConnection,
+    collection: &str,
+    mut record: Map<String, Value>,
+    on_conflict: OnConflict,
+) -> Result<bool, String> {
+    if record.get("id").and_then(Value::as_str).unwrap_or_default().is_empty() {
+        return Err("Missing id".to_string());
+    }
+    if let Some(column) = record.keys().find(|column| !is_identifier(column)) {
+        return Err(format!("Invalid field {column:?}"));
+    }
+
+    validate(collection, &record)?;
+
+    // Timestamps are stored in the format the API writes, whatever the one of
+    // the dataset
+    let has_created = record.get("created").is_some_and(|created| !created.is_null());
+    let now = current_timestamp();
+    for column in ["created", "updated"] {
+        let timestamp = match record.get(column) {
+            None | Some(Value::Null) => now.clone(),
+            Some(Value::String(value)) => {
+                normalize_timestamp(value).ok_or_else(|| format!("{column}: Must be a valid datetime."))?
+            }
+            Some(_) => return Err(format!("{column}: Must be a valid datetime.")),
+        };
+        record.insert(column.to_string(), Value::String(timestamp));
+    }
+
+    // Fixture users may be given a plain text password
+    if collection == "users" {
+        if let Some(password) = record.remove("password") {
+            let Value::String(password) = password else {
+                return Err("password: must be a string".to_string());
+            };
+            let hash = hash_password(password).await.map_err(|err| describe(&err))?;
+            record.insert("password_hash".to_string(), Value::String(hash));
+        }
+    }
+
+    let columns: Vec<&String> = record.keys().collect();
+    let conflict = match on_conflict {
+        OnConflict::Skip => "DO NOTHING".to_string(),
+        OnConflict::Update => {
+            let updates: Vec<String> = columns
+                .iter()
+                .filter(|column| column.as_str() != "id" && (has_created || column.as_str() != "created"))
+                .map(|column| format!("\"{column}\" = excluded.\"{column}\""))
+                .collect();
+            if updates.is_empty() {
+                "DO NOTHING".to_string()
+            } else {
+                format!("DO UPDATE SET {}", updates.join(", "))
+            }
+        }
+    };
+    let sql = format!(
+        "INSERT INTO {collection} ({}) VALUES ({}) ON CONFLICT (id) {conflict}",
+        columns
+            .iter()
+            .map(|column| format!("\"{column}\""))
+            .collect::<Vec<_>>()
+            .join(", "),
+        vec!["?"; columns.len()].join(", "),
+    );
+
+    let mut query = sqlx::query(&sql);
+    for value in record.values() {
+        query = bind_value(query, value);
+    }
+    match query.execute(&mut *conn).await {
+        Ok(result) => Ok(result.rows_affected() > 0),
+        Err(err) if is_foreign_key_violation(&err) => {
+            let fields = missing_relations(conn, collection, &record)
+                .await
+                .map_err(|err| describe(&err))?;
+            Err(format!("Failed to find the related records for {}.", fields.join(", ")))
+        }
+        Err(err) => Err(describe(&Error::from(err))),
+    }
+}
+
+fn validate(collection: &str, record: &Map<String, Value>) -> Result<(), String> {
+    match collection {
+        "users" => check::<User>(record),
+        "categories" => check::<Category>(record),
+        "subcategories" => check::<Subcategory>(record),
+        "stores" => check::<Store>(record),
+        "products" => check::<Product>(record),
+        "carts" => check::<Cart>(record),
+        "cart_items" => check::<CartItem>(record),
+        "addresses" => check::<Address>(record),
+        "orders" => check::<Order>(record),
+        "customers" => check::<Customer>(record),
+        "notifications" => check::<Notification>(record),
+        _ => Err(format!("Unknown collection {collection:?}")),
+    }
+}
+
+/// Validates `record` as a `T`, with the fields it omits taken from the
+/// model's defaults.
+fn check<T>(record: &Map<String, Value>) -> Result<(), String>
+where
+    T: Default + Serialize + DeserializeOwned + Validate,
+{
+    let mut full = match serde_json::to_value(T::default()) {
+        Ok(Value::Object(full)) => full,
+        _ => return Err("Failed to build the record".to_string()),
+    };
+    for (column, value) in record {
+        if let Some(field) = full.get_mut(column) {
+            *field = match value {
+                // JSON columns such as `images` are stored as text
+                Value::Array(_) | Value::Object(_) => Value::String(value.to_string()),
+                // Left to the validation rules and the NOT NULL constraints
+                Value::Null if !field.is_null() => continue,
+                _ => value.clone(),
+            };
+        }
+    }
+
+    let typed: T = serde_json::from_value(Value::Object(full)).map_err(|err| format!("Invalid record: {err}"))?;
+    typed.validate().map_err(|err| describe(&Error::from(err)))
+}
+
+/// Formats an error with its field details, e.g.
+/// `Failed to validate the submitted data. (email: Must be a valid email address.)`
+fn describe(err: &Error) -> String {
+    let fields: Vec<String> = err
+        .data()
+        .iter()
+        .map(|(field, detail)| {
+            let message = detail.get("message").and_then(Value::as_str).unwrap_or("Invalid value.");
+            format!("{field}: {message}")
+        })
+        .collect();
+
+    if fields.is_empty() {
+        err.to_string()
+    } else {
+        format!("{} ({})", err, fields.join(", "))
+    }
+}
@@ -1,4 +1,17 @@
 pub mod user;
+pub mod api_key;
+pub mod audit;
+pub mod file;
+pub mod filter;
+pub mod fixture;
+pub mod order_status;
 pub mod pocketbase;
+pub mod realtime;
 pub mod auth;
+pub mod cart;
+pub mod checkout;
+pub mod password;
+pub mod record;
+pub mod storage;
+pub mod superuser;
 pub mod util;
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, SqliteConnection};
+use std::fmt;
+
+use crate::errors::{Conflict, Error};
+use crate::models::pocketbase::{current_timestamp, generate_id, Order};
+use crate::models::record;
+use crate::utils::auth::Auth;
+
+/// The lifecycle of an order. Orders start `pending` and end `delivered`,
+/// `cancelled` or `refunded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Processing,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Processing => "processing",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        let status = match status {
+            "pending" => OrderStatus::Pending,
+            "paid" => OrderStatus::Paid,
+            "processing" => OrderStatus::Processing,
+            "shipped" => OrderStatus::Shipped,
+            "delivered" => OrderStatus::Delivered,
+            "cancelled" => OrderStatus::Cancelled,
+            "refunded" => OrderStatus::Refunded,
+            _ => return None,
+        };
+
+        Some(status)
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Who may perform a transition, besides superusers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Party {
+    /// The user who placed the order.
+    Buyer,
+    /// The owner of the store the order was placed with.
+    Store,
+}
+
+/// The allowed transitions and the parties that may perform them.
+const TRANSITIONS: &[(OrderStatus, OrderStatus, &[Party])] = &[
+    (OrderStatus::Pending, OrderStatus::Paid, &[Party::Store]),
+    (OrderStatus::Pending, OrderStatus::Cancelled, &[Party::Buyer, Party::Store]),
+    (OrderStatus::Paid, OrderStatus::Processing, &[Party::Store]),
+    (OrderStatus::Paid, OrderStatus::Refunded, &[Party::Store]),
+    (OrderStatus::Processing, OrderStatus::Shipped, &[Party::Store]),
+    (OrderStatus::Processing, OrderStatus::Refunded, &[Party::Store]),
+    (OrderStatus::Shipped, OrderStatus::Delivered, &[Party::Buyer, Party::Store]),
+    (OrderStatus::Delivered, OrderStatus::Refunded, &[Party::Store]),
+];
+
+/// A status change of an order.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderStatusChange {
+    pub id: String,
+    pub order: String,
+    pub from_status: String,
+    pub to_status: String,
+    /// User or superuser who changed the status.
+    pub actor: String,
+    pub note: Option<String>,
+    pub created: String,
+}
+
+/// A line of `orders.items`, as far as restocking is concerned.
+#[derive(Debug, Deserialize)]
+struct StockedItem {
+    product: String,
+    quantity: i64,
+}
+
+/// Moves an order to the status `to`, if the lifecycle allows it and `auth`
+/// is a party allowed to. The change is recorded in the order's history,
+/// and the items of a cancelled checkout order are put back in stock.
+pub async fn transition(
+    conn: &mut SqliteConnection,
+    auth: &Auth,
+    id: &str,
+    to: OrderStatus,
+    note: Option<String>,
+) -> Result<Order, Error> {
+    let mut tx = conn.begin().await?;
+    // Orders that cannot be viewed are not found
+    let order = record::view::<Order>(&mut tx, Some(auth), id).await?;
+    let actor = match auth {
+        Auth::User(user) | Auth::Superuser(user) => user.id.clone(),
+        Auth::ApiKey(_) => return Err(Error::forbidden()),
+    };
+
+    let from = OrderStatus::parse(&order.status);
+    let Some((_, _, parties)) = TRANSITIONS
+        .iter()
+        .find(|(source, target, _)| Some(*source) == from && *target == to)
+    else {
+        let message = format!("The order cannot go from {} to {}.", order.status, to);
+        return Err(Conflict::new(message).into());
+    };
+    if !auth.is_superuser() {
+        let store_owner: Option<String> = sqlx::query_scalar("SELECT user FROM stores WHERE id = ?")
+            .bind(&order.store)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let is_party = |party: &Party| match party {
+            Party::Buyer => order.user.as_deref() == Some(actor.as_str()),
+            Party::Store => store_owner.as_deref() == Some(actor.as_str()),
+        };
+        if !parties.iter().any(is_party) {
+            return Err(Error::forbidden());
+        }
+    }
+
+    let now = current_timestamp();
+    // Guards against a concurrent change since the order was read
+    let updated = sqlx::query("UPDATE orders SET status = ?, updated = ? WHERE id = ? AND status = ?")
+        .bind(to.as_str())
+        .bind(&now)
+        .bind(id)
+        .bind(&order.status)
+        .execute(&mut *tx)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Conflict::new("The order status has changed, please try again.").into());
+    }
+
+    // Only the items checkout took out of stock are put back
+    if to == OrderStatus::Cancelled && order.checkout.is_some() {
+        let items: Vec<StockedItem> = serde_json::from_str(&order.items).map_err(anyhow::Error::from)?;
+        for item in items {
+            // Products deleted since are skipped
+            sqlx::query("UPDATE products SET inventory = inventory + ?, updated = ? WHERE id = ?")
+                .bind(item.quantity)
+                .bind(&now)
+                .bind(&item.product)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    sqlx::query(
+        "INSERT INTO order_status_history (id, \"order\", from_status, to_status, actor, note, created) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(generate_id())
+    .bind(id)
+    .bind(&order.status)
+    .bind(to.as_str())
+    .bind(&actor)
+    .bind(&note)
+    .bind(&now)
+    .execute(&mut *tx)
+    .await?;
+
+    let order = record::find::<Order>(&mut tx, id).await?.ok_or_else(Error::not_found)?;
+    tx.commit().await?;
+
+    Ok(order)
+}
+
+/// The status changes of an order `auth` may view, oldest first.
+pub async fn history(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+) -> Result<Vec<OrderStatusChange>, Error> {
+    record::view::<Order>(conn, auth, id).await?;
+    let changes = sqlx::query_as::<_, OrderStatusChange>(
+        "SELECT * FROM order_status_history WHERE \"order\" = ? ORDER BY created, rowid",
+    )
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(changes)
+}
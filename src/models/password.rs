@@ -0,0 +1,226 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{self, PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::fs;
+
+use crate::errors::{HashPasswordError, PasswordPolicyError};
+use crate::settings::{self, PasswordAlgorithm, SETTINGS};
+
+pub static PASSWORDS: Lazy<Passwords> = Lazy::new(|| {
+    Passwords::from_settings(&SETTINGS.auth.password).expect("Failed to setup password hashing")
+});
+
+pub static PASSWORD_POLICY: Lazy<PasswordPolicy> = Lazy::new(|| {
+    PasswordPolicy::from_settings(&SETTINGS.auth.password).expect("Failed to load password policy")
+});
+
+/// A password hashing algorithm.
+pub trait PasswordHasher: Send + Sync {
+    /// Returns true if `hash` was produced by this algorithm.
+    fn recognizes(&self, hash: &str) -> bool;
+
+    fn hash(&self, password: &str) -> Result<String, HashPasswordError>;
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, HashPasswordError>;
+
+    /// Returns true if `hash` was produced with different parameters than the
+    /// configured ones.
+    fn is_outdated(&self, hash: &str) -> bool;
+}
+
+pub struct Argon2idHasher {
+    params: Params,
+}
+
+impl Argon2idHasher {
+    pub fn new(settings: &settings::Argon2) -> Result<Self, HashPasswordError> {
+        let params = Params::new(
+            settings.memory_cost,
+            settings.time_cost,
+            settings.parallelism,
+            None,
+        )
+        .map_err(password_hash::Error::from)?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2idHasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$argon2id$")
+    }
+
+    fn hash(&self, password: &str) -> Result<String, HashPasswordError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self.argon2().hash_password(password.as_bytes(), &salt)?;
+        Ok(hash.to_string())
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, HashPasswordError> {
+        let parsed = PasswordHash::new(hash)?;
+        // Verification uses the parameters encoded in the hash itself.
+        match self.argon2().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+
+        parsed.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+    }
+}
+
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Self {
+        Self { cost }
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn recognizes(&self, hash: &str) -> bool {
+        ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+    }
+
+    fn hash(&self, password: &str) -> Result<String, HashPasswordError> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, HashPasswordError> {
+        Ok(bcrypt::verify(password, hash)?)
+    }
+
+    fn is_outdated(&self, hash: &str) -> bool {
+        hash.parse::<bcrypt::HashParts>()
+            .map(|parts| parts.get_cost() != self.cost)
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Mismatch,
+    Match,
+    /// The password matches but the hash should be replaced by one produced
+    /// with the current algorithm and parameters.
+    MatchNeedsRehash,
+}
+
+/// Hashes passwords with the configured algorithm while still verifying
+/// hashes produced by the other supported ones.
+pub struct Passwords {
+    /// The first hasher is the current one.
+    hashers: Vec<Box<dyn PasswordHasher>>,
+    dummy_hash: String,
+}
+
+impl Passwords {
+    pub fn from_settings(settings: &settings::Password) -> Result<Self, HashPasswordError> {
+        let argon2: Box<dyn PasswordHasher> = Box::new(Argon2idHasher::new(&settings.argon2)?);
+        let bcrypt: Box<dyn PasswordHasher> = Box::new(BcryptHasher::new(settings.bcrypt_cost));
+
+        let hashers = match settings.algorithm {
+            PasswordAlgorithm::Argon2id => vec![argon2, bcrypt],
+            PasswordAlgorithm::Bcrypt => vec![bcrypt, argon2],
+        };
+        let dummy_hash = hashers[0].hash("dummy password")?;
+
+        Ok(Self {
+            hashers,
+            dummy_hash,
+        })
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, HashPasswordError> {
+        self.hashers[0].hash(password)
+    }
+
+    pub fn verify(&self, password: &str, hash: &str) -> Result<Verification, HashPasswordError> {
+        let (index, hasher) = self
+            .hashers
+            .iter()
+            .enumerate()
+            .find(|(_, hasher)| hasher.recognizes(hash))
+            .ok_or(HashPasswordError::UnknownFormat)?;
+
+        if !hasher.verify(password, hash)? {
+            return Ok(Verification::Mismatch);
+        }
+
+        if index > 0 || hasher.is_outdated(hash) {
+            Ok(Verification::MatchNeedsRehash)
+        } else {
+            Ok(Verification::Match)
+        }
+    }
+
+    /// Verifies `password` against a throwaway hash so that lookups for
+    /// unknown accounts take as long as lookups for existing ones.
+    pub fn verify_dummy(&self, password: &str) {
+        let _ = self.verify(password, &self.dummy_hash);
+    }
+}
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn from_settings(settings: &settings::Password) -> std::io::Result<Self> {
+        let breached = match &settings.breached_list {
+            Some(path) => fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        Ok(Self {
+            min_length: settings.min_length,
+            max_length: settings.max_length,
+            breached,
+        })
+    }
+
+    pub fn check(&self, password: &str) -> Result<(), PasswordPolicyError> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PasswordPolicyError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(PasswordPolicyError::TooLong(self.max_length));
+        }
+        if self.breached.contains(&password.to_lowercase()) {
+            return Err(PasswordPolicyError::Breached);
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,23 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::errors::Error;
+use crate::models::api_key::ApiKeyScope;
+use crate::models::file::{FileField, IMAGE_TYPES};
+use crate::models::record::{Action, Record, Rules};
+
+/// Condition matching records whose `store` column belongs to the
+/// authenticated user or to the API key used.
+const STORE_OWNER_RULE: &str = "store IN (SELECT id FROM stores WHERE user = @request.auth.id) OR store = @request.auth.store";
+
+/// Maximum size of an uploaded image.
+const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+
+/// Maximum size of an invoice or of a file delivered with an order.
+const MAX_DOCUMENT_SIZE: usize = 20 * 1024 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BaseRecord {
     pub id: String,
@@ -14,6 +30,7 @@ pub struct BaseRecord {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct User {
     pub id: String,
     #[validate(email)]
@@ -31,6 +48,7 @@ pub struct User {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Category {
     pub id: String,
     #[validate(length(min = 1))]
@@ -45,7 +63,7 @@ pub struct Category {
     pub collection_name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, sqlx::FromRow)]
 pub struct Subcategory {
     pub id: String,
     #[validate(length(min = 1))]
@@ -61,6 +79,7 @@ pub struct Subcategory {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Store {
     pub id: String,
     #[validate(length(min = 1, max = 50))]
@@ -83,6 +102,7 @@ pub struct Store {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Product {
     pub id: String,
     #[validate(length(min = 1))]
@@ -103,7 +123,8 @@ pub struct Product {
     pub collection_name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Cart {
     pub id: String,
     pub user: Option<String>,
@@ -115,11 +136,12 @@ pub struct Cart {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct CartItem {
     pub id: String,
     pub cart: String,
     pub product: String,
-    #[validate(range(min = 1))]
+    #[validate(range(min = 1, max = 1000))]
     pub quantity: i32,
     pub subcategory: Option<String>,
     pub created: String,
@@ -129,6 +151,7 @@ pub struct CartItem {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Address {
     pub id: String,
     #[validate(length(min = 1))]
@@ -150,6 +173,7 @@ pub struct Address {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Order {
     pub id: String,
     pub user: Option<String>,
@@ -165,6 +189,10 @@ pub struct Order {
     pub email: String,
     pub address: String,
     pub notes: Option<String>,
+    pub invoice: Option<String>,
+    pub downloads: Option<String>, // JSON array as string
+    /// Shared by the orders placed by the same checkout, one per store.
+    pub checkout: Option<String>,
     pub created: String,
     pub updated: String,
     pub collection_id: String,
@@ -172,12 +200,15 @@ pub struct Order {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[serde(default)]
 pub struct Customer {
     pub id: String,
     pub name: Option<String>,
     #[validate(email)]
     pub email: String,
     pub store: String,
+    /// Account of the buyer, for the customers counted by checkout.
+    pub user: Option<String>,
     pub total_orders: i32,
     pub total_spent: String,
     pub created: String,
@@ -186,7 +217,7 @@ pub struct Customer {
     pub collection_name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, sqlx::FromRow)]
 pub struct Notification {
     pub id: String,
     #[validate(email)]
@@ -229,17 +260,69 @@ pub struct ExpandData {
 }
 
 // Helper functions for PocketBase compatibility
+
+/// Length of record ids.
+pub const ID_LENGTH: usize = 15;
+
+const ID_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Random PocketBase style id, e.g. `k8w2qn0xz3jv5ta`.
 pub fn generate_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    format!("{:x}", timestamp)[..15].to_string()
+    let mut rng = rand::thread_rng();
+    (0..ID_LENGTH)
+        .map(|_| ID_ALPHABET[rng.gen_range(0..ID_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Checks an id chosen by a client, which must have the same format as the
+/// generated ones.
+pub fn validate_id(id: &str) -> Result<(), Error> {
+    if id.len() != ID_LENGTH {
+        return Err(Error::invalid_field(
+            "id",
+            "validation_length_out_of_range",
+            &format!("Must be exactly {ID_LENGTH} characters."),
+        ));
+    }
+    if !id.bytes().all(|byte| ID_ALPHABET.contains(&byte)) {
+        return Err(Error::invalid_field(
+            "id",
+            "validation_invalid_format",
+            "Must contain only lowercase letters and digits.",
+        ));
+    }
+
+    Ok(())
 }
 
+/// Format of the stored timestamps, UTC with milliseconds.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3fZ";
+
 pub fn current_timestamp() -> String {
-    Utc::now().format("%Y-%m-%d %H:%M:%S%.3fZ").to_string()
+    Utc::now().format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Converts a date or datetime, such as SQLite's `CURRENT_TIMESTAMP` or an
+/// RFC 3339 value with an offset, to the stored timestamp format. Values
+/// without an offset are taken as UTC.
+pub fn normalize_timestamp(value: &str) -> Option<String> {
+    let value = value.trim();
+    let datetime = match DateTime::parse_from_rfc3339(value) {
+        Ok(datetime) => datetime.naive_utc(),
+        Err(_) => {
+            let naive = value.strip_suffix('Z').unwrap_or(value);
+            ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(naive, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(naive, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })?
+        }
+    };
+
+    Some(datetime.and_utc().format(TIMESTAMP_FORMAT).to_string())
 }
 
 pub fn calculate_total_pages(total_items: i32, per_page: i32) -> i32 {
@@ -270,6 +353,38 @@ impl User {
     }
 }
 
+impl Default for User {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), None)
+    }
+}
+
+impl Record for User {
+    const COLLECTION: &'static str = "users";
+    const HIDDEN_COLUMNS: &'static [&'static str] = &["password_hash"];
+    /// Only changed once the user proves they own the address.
+    const MANAGED_FIELDS: &'static [&'static str] = &["email", "verified"];
+    const FILE_FIELDS: &'static [FileField] = &[FileField {
+        name: "avatar",
+        max_select: 1,
+        max_size: MAX_IMAGE_SIZE,
+        mime_types: IMAGE_TYPES,
+        thumbs: &["100x100"],
+        protected: false,
+    }];
+    const RULES: Rules = Rules {
+        list: Some("id = @request.auth.id"),
+        view: Some("id = @request.auth.id"),
+        create: Some(""),
+        update: Some("id = @request.auth.id"),
+        delete: Some("id = @request.auth.id"),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 impl Category {
     pub fn new(name: String, slug: String, description: Option<String>, image: Option<String>) -> Self {
         let now = current_timestamp();
@@ -287,6 +402,35 @@ impl Category {
     }
 }
 
+impl Default for Category {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), None, None)
+    }
+}
+
+impl Record for Category {
+    const COLLECTION: &'static str = "categories";
+    const FILE_FIELDS: &'static [FileField] = &[FileField {
+        name: "image",
+        max_select: 1,
+        max_size: MAX_IMAGE_SIZE,
+        mime_types: IMAGE_TYPES,
+        thumbs: &["100x100", "400x0"],
+        protected: false,
+    }];
+    const RULES: Rules = Rules {
+        list: Some(""),
+        view: Some(""),
+        create: None,
+        update: None,
+        delete: None,
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 impl Store {
     pub fn new(name: String, slug: String, user: String) -> Self {
         let now = current_timestamp();
@@ -311,6 +455,27 @@ impl Store {
     }
 }
 
+impl Default for Store {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), String::new())
+    }
+}
+
+impl Record for Store {
+    const COLLECTION: &'static str = "stores";
+    const RULES: Rules = Rules {
+        list: Some(""),
+        view: Some(""),
+        create: Some("user = @request.auth.id"),
+        update: Some("user = @request.auth.id"),
+        delete: Some("user = @request.auth.id"),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 impl Product {
     pub fn new(name: String, price: String, category: String, store: String) -> Self {
         let now = current_timestamp();
@@ -334,6 +499,42 @@ impl Product {
     }
 }
 
+impl Default for Product {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), String::new(), String::new())
+    }
+}
+
+impl Record for Product {
+    const COLLECTION: &'static str = "products";
+    const FILE_FIELDS: &'static [FileField] = &[FileField {
+        name: "images",
+        max_select: 10,
+        max_size: MAX_IMAGE_SIZE,
+        mime_types: IMAGE_TYPES,
+        thumbs: &["100x100", "400x400", "400x400t", "400x400f", "800x0", "0x400"],
+        protected: false,
+    }];
+    const RULES: Rules = Rules {
+        list: Some(""),
+        view: Some(""),
+        create: Some(STORE_OWNER_RULE),
+        update: Some(STORE_OWNER_RULE),
+        delete: Some(STORE_OWNER_RULE),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn api_key_scope(action: Action) -> Option<ApiKeyScope> {
+        match action {
+            Action::List | Action::View => Some(ApiKeyScope::ProductsRead),
+            Action::Create | Action::Update | Action::Delete => Some(ApiKeyScope::ProductsWrite),
+        }
+    }
+}
+
 impl Cart {
     pub fn new(user: Option<String>, session_id: Option<String>) -> Self {
         let now = current_timestamp();
@@ -349,6 +550,75 @@ impl Cart {
     }
 }
 
+/// Carts of the authenticated user. Guest carts are only reachable through
+/// the cart endpoints, with their session cookie.
+const CART_OWNER_RULE: &str = "user = @request.auth.id";
+const CART_ITEM_OWNER_RULE: &str = "cart IN (SELECT id FROM carts WHERE user = @request.auth.id)";
+/// Like the cart endpoints, only active products can be put in a cart.
+const CART_ITEM_WRITE_RULE: &str =
+    "cart IN (SELECT id FROM carts WHERE user = @request.auth.id) AND product IN (SELECT id FROM products WHERE active)";
+
+impl Default for Cart {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl Record for Cart {
+    const COLLECTION: &'static str = "carts";
+    /// Identifies guest carts, set by the cart endpoints.
+    const MANAGED_FIELDS: &'static [&'static str] = &["session_id"];
+    const RULES: Rules = Rules {
+        list: Some(CART_OWNER_RULE),
+        view: Some(CART_OWNER_RULE),
+        create: Some(CART_OWNER_RULE),
+        update: Some(CART_OWNER_RULE),
+        delete: Some(CART_OWNER_RULE),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl CartItem {
+    pub fn new(cart: String, product: String, quantity: i32) -> Self {
+        let now = current_timestamp();
+        Self {
+            id: generate_id(),
+            cart,
+            product,
+            quantity,
+            subcategory: None,
+            created: now.clone(),
+            updated: now,
+            collection_id: "cart_items".to_string(),
+            collection_name: "cart_items".to_string(),
+        }
+    }
+}
+
+impl Default for CartItem {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), 1)
+    }
+}
+
+impl Record for CartItem {
+    const COLLECTION: &'static str = "cart_items";
+    const RULES: Rules = Rules {
+        list: Some(CART_ITEM_OWNER_RULE),
+        view: Some(CART_ITEM_OWNER_RULE),
+        create: Some(CART_ITEM_WRITE_RULE),
+        update: Some(CART_ITEM_WRITE_RULE),
+        delete: Some(CART_ITEM_OWNER_RULE),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 impl Address {
     pub fn new(line1: String, city: String, state: String, postal_code: String, country: String, user: String) -> Self {
         let now = current_timestamp();
@@ -367,4 +637,146 @@ impl Address {
             collection_name: "addresses".to_string(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for Address {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), String::new(), String::new(), String::new(), String::new())
+    }
+}
+
+impl Record for Address {
+    const COLLECTION: &'static str = "addresses";
+    const RULES: Rules = Rules {
+        list: Some("user = @request.auth.id"),
+        view: Some("user = @request.auth.id"),
+        create: Some("user = @request.auth.id"),
+        update: Some("user = @request.auth.id"),
+        delete: Some("user = @request.auth.id"),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        let now = current_timestamp();
+        Self {
+            id: generate_id(),
+            user: None,
+            store: String::new(),
+            items: "[]".to_string(),
+            quantity: None,
+            amount: String::new(),
+            status: "pending".to_string(),
+            name: String::new(),
+            email: String::new(),
+            address: String::new(),
+            notes: None,
+            invoice: None,
+            downloads: None,
+            checkout: None,
+            created: now.clone(),
+            updated: now,
+            collection_id: "orders".to_string(),
+            collection_name: "orders".to_string(),
+        }
+    }
+}
+
+impl Record for Order {
+    const COLLECTION: &'static str = "orders";
+    /// The status changes through the transitions of `order_status`. The
+    /// other fields are set by checkout and restocked from when cancelling.
+    const MANAGED_FIELDS: &'static [&'static str] =
+        &["status", "user", "store", "items", "quantity", "amount", "checkout"];
+    const FILE_FIELDS: &'static [FileField] = &[
+        FileField {
+            name: "invoice",
+            max_select: 1,
+            max_size: MAX_DOCUMENT_SIZE,
+            mime_types: &["application/pdf"],
+            thumbs: &[],
+            protected: true,
+        },
+        // Digital products delivered to the customer
+        FileField {
+            name: "downloads",
+            max_select: 10,
+            max_size: MAX_DOCUMENT_SIZE,
+            mime_types: &["application/pdf", "application/zip", "application/epub+zip"],
+            thumbs: &[],
+            protected: true,
+        },
+    ];
+    const RULES: Rules = Rules {
+        list: Some("user = @request.auth.id OR store IN (SELECT id FROM stores WHERE user = @request.auth.id) OR store = @request.auth.store"),
+        view: Some("user = @request.auth.id OR store IN (SELECT id FROM stores WHERE user = @request.auth.id) OR store = @request.auth.store"),
+        // Placed through checkout, which checks the cart and the stock
+        create: None,
+        update: Some("store IN (SELECT id FROM stores WHERE user = @request.auth.id)"),
+        delete: None,
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn api_key_scope(action: Action) -> Option<ApiKeyScope> {
+        match action {
+            Action::List | Action::View => Some(ApiKeyScope::OrdersRead),
+            Action::Create | Action::Update | Action::Delete => None,
+        }
+    }
+}
+
+impl Customer {
+    pub fn new(email: String, store: String, name: Option<String>) -> Self {
+        let now = current_timestamp();
+        Self {
+            id: generate_id(),
+            name,
+            email,
+            store,
+            user: None,
+            total_orders: 0,
+            total_spent: "0".to_string(),
+            created: now.clone(),
+            updated: now,
+            collection_id: "customers".to_string(),
+            collection_name: "customers".to_string(),
+        }
+    }
+}
+
+impl Default for Customer {
+    fn default() -> Self {
+        Self::new(String::new(), String::new(), None)
+    }
+}
+
+impl Record for Customer {
+    const COLLECTION: &'static str = "customers";
+    /// The buyer account and totals of the orders placed through checkout.
+    const MANAGED_FIELDS: &'static [&'static str] = &["user", "total_orders", "total_spent"];
+    const RULES: Rules = Rules {
+        list: Some(STORE_OWNER_RULE),
+        view: Some(STORE_OWNER_RULE),
+        create: Some(STORE_OWNER_RULE),
+        update: Some(STORE_OWNER_RULE),
+        delete: Some(STORE_OWNER_RULE),
+    };
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn api_key_scope(action: Action) -> Option<ApiKeyScope> {
+        match action {
+            Action::List | Action::View => Some(ApiKeyScope::OrdersRead),
+            Action::Create | Action::Update | Action::Delete => None,
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use chrono::Utc;
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+
+use crate::errors::Error;
+use crate::models::api_key::ApiKey;
+use crate::models::pocketbase::{Category, Order, Product, Store, User};
+use crate::models::record::{self, Record};
+use crate::utils::auth::Auth;
+
+/// Collections whose changes can be subscribed to.
+pub const COLLECTIONS: [&str; 5] = ["users", "categories", "stores", "products", "orders"];
+
+/// Messages buffered per client. Clients that fall further behind are
+/// disconnected rather than slowing down the others.
+const CLIENT_BUFFER: usize = 64;
+
+const CLIENT_ID_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// A message sent to a client, named after the subscription it matches.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub name: String,
+    pub data: Value,
+}
+
+/// A `collection/*` or `collection/recordId` topic, optionally followed by
+/// `?options=` and the URL encoded JSON `{"query": {"filter": "...", "expand": "..."}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub collection: String,
+    pub record_id: Option<String>,
+    pub filter: Option<String>,
+    pub expand: Option<String>,
+}
+
+impl Subscription {
+    pub fn parse(topic: &str) -> Option<Self> {
+        let (path, query) = topic.split_once('?').unwrap_or((topic, ""));
+        let (collection, record_id) = path.split_once('/')?;
+        if !COLLECTIONS.contains(&collection) || record_id.is_empty() {
+            return None;
+        }
+
+        let mut subscription = Self {
+            collection: collection.to_string(),
+            record_id: (record_id != "*").then(|| record_id.to_string()),
+            filter: None,
+            expand: None,
+        };
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            if key != "options" {
+                continue;
+            }
+            let options: Value = serde_json::from_str(&value).ok()?;
+            let option = |name: &str| {
+                options["query"][name]
+                    .as_str()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(str::to_string)
+            };
+            subscription.filter = option("filter");
+            subscription.expand = option("expand");
+        }
+
+        Some(subscription)
+    }
+
+    fn matches(&self, collection: &str, id: &str) -> bool {
+        self.collection == collection && self.record_id.as_deref().is_none_or(|record_id| record_id == id)
+    }
+}
+
+struct Client {
+    auth: Option<Auth>,
+    subscriptions: Vec<(String, Subscription)>,
+    sender: mpsc::Sender<Message>,
+}
+
+/// A subscription matching a change.
+struct Recipient {
+    client_id: String,
+    auth: Option<Auth>,
+    topic: String,
+    subscription: Subscription,
+    sender: mpsc::Sender<Message>,
+}
+
+struct Change {
+    collection: String,
+    action: EventAction,
+    record: Map<String, Value>,
+}
+
+#[derive(Default)]
+struct Clients(Mutex<HashMap<String, Client>>);
+
+impl Clients {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Client>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Keeps track of the connected realtime clients and delivers record
+/// changes to their subscriptions, in the order they were published.
+#[derive(Clone)]
+pub struct Realtime {
+    clients: Arc<Clients>,
+    changes: mpsc::UnboundedSender<Change>,
+}
+
+impl Realtime {
+    /// Starts delivering changes, evaluating the view rules and filters of the
+    /// subscriptions against `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        let (changes, mut receiver) = mpsc::unbounded_channel::<Change>();
+        let clients = Arc::<Clients>::default();
+
+        // Stops once every handle, and so the sender, is dropped
+        let worker_clients = clients.clone();
+        tokio::spawn(async move {
+            while let Some(change) = receiver.recv().await {
+                if let Err(err) = deliver(&worker_clients, &pool, change).await {
+                    warn!("Failed to deliver realtime event: {}", err);
+                }
+            }
+        });
+
+        Self { clients, changes }
+    }
+
+    /// Registers a client and returns its id and the receiver of its
+    /// messages. The client is forgotten once the receiver is dropped.
+    pub fn connect(&self) -> (String, mpsc::Receiver<Message>) {
+        let client_id = Alphanumeric.sample_string(&mut rand::thread_rng(), CLIENT_ID_LENGTH);
+        let (sender, receiver) = mpsc::channel(CLIENT_BUFFER);
+        self.clients.lock().insert(
+            client_id.clone(),
+            Client {
+                auth: None,
+                subscriptions: Vec::new(),
+                sender,
+            },
+        );
+
+        (client_id, receiver)
+    }
+
+    pub fn disconnect(&self, client_id: &str) {
+        self.clients.lock().remove(client_id);
+    }
+
+    /// Replaces the subscriptions of a client. The client is bound to the
+    /// credentials of its first subscription request and may not switch to
+    /// other ones.
+    pub fn subscribe(
+        &self,
+        client_id: &str,
+        auth: Option<Auth>,
+        subscriptions: Vec<(String, Subscription)>,
+    ) -> Result<(), Error> {
+        let mut clients = self.clients.lock();
+        let client = clients
+            .get_mut(client_id)
+            .ok_or_else(Error::not_found)?;
+
+        let identity = |auth: &Option<Auth>| auth.as_ref().map(identity);
+        if client.auth.is_some() && identity(&client.auth) != identity(&auth) {
+            return Err(Error::forbidden());
+        }
+        if auth.is_some() {
+            client.auth = auth;
+        }
+        client.subscriptions = subscriptions;
+
+        Ok(())
+    }
+
+    /// Queues a record change for delivery.
+    pub fn publish(&self, collection: &str, action: EventAction, record: Value) {
+        let Value::Object(record) = record else {
+            return;
+        };
+        let change = Change {
+            collection: collection.to_string(),
+            action,
+            record,
+        };
+        // Only fails once the worker is gone, i.e. on shutdown
+        let _ = self.changes.send(change);
+    }
+}
+
+async fn deliver(clients: &Clients, pool: &SqlitePool, change: Change) -> Result<(), Error> {
+    let Some(id) = change.record.get("id").and_then(Value::as_str) else {
+        return Ok(());
+    };
+
+    // Collected first, the rules are evaluated without holding the lock
+    let recipients: Vec<Recipient> = clients
+        .lock()
+        .iter()
+        .flat_map(|(client_id, client)| {
+            client
+                .subscriptions
+                .iter()
+                .filter(|(_, subscription)| subscription.matches(&change.collection, id))
+                .map(|(topic, subscription)| Recipient {
+                    client_id: client_id.clone(),
+                    auth: client.auth.clone(),
+                    topic: topic.clone(),
+                    subscription: subscription.clone(),
+                    sender: client.sender.clone(),
+                })
+        })
+        .collect();
+    if recipients.is_empty() {
+        return Ok(());
+    }
+
+    // Tokens expire and keys get revoked while clients stay connected
+    let mut credentials: HashMap<String, Option<Auth>> = HashMap::new();
+    for recipient in &recipients {
+        let Some(auth) = &recipient.auth else {
+            continue;
+        };
+        if credentials.contains_key(&recipient.client_id) {
+            continue;
+        }
+        let current = current_credentials(pool, auth).await?;
+        if current.is_none() {
+            clients.lock().remove(&recipient.client_id);
+        }
+        credentials.insert(recipient.client_id.clone(), current);
+    }
+
+    let mut conn = pool.acquire().await?;
+    for Recipient {
+        client_id,
+        auth,
+        topic,
+        subscription,
+        sender,
+    } in recipients
+    {
+        let auth = match auth {
+            Some(_) => match credentials.get(&client_id).cloned().flatten() {
+                Some(auth) => Some(auth),
+                None => continue,
+            },
+            None => None,
+        };
+        let visible = can_view(
+            &mut conn,
+            auth.as_ref(),
+            &change.collection,
+            &change.record,
+            subscription.filter.as_deref(),
+        )
+        .await
+        .unwrap_or(false);
+        if !visible {
+            continue;
+        }
+
+        let mut record = Value::Object(change.record.clone());
+        if let Some(expand) = &subscription.expand {
+            let expanded = expand_relations(&mut conn, auth.as_ref(), &change.collection, &change.record, expand)
+                .await
+                .unwrap_or_default();
+            if !expanded.is_empty() {
+                record["expand"] = Value::Object(expanded);
+            }
+        }
+
+        let message = Message {
+            name: topic,
+            data: json!({ "action": change.action, "record": record }),
+        };
+        match sender.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!("Disconnecting realtime client {} which is not keeping up", client_id);
+                clients.lock().remove(&client_id);
+            }
+            Err(TrySendError::Closed(_)) => {
+                clients.lock().remove(&client_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The credentials of a client as of now, or `None` once its access token
+/// expired or its API key was revoked.
+async fn current_credentials(pool: &SqlitePool, auth: &Auth) -> Result<Option<Auth>, Error> {
+    match auth {
+        Auth::User(user) | Auth::Superuser(user) => {
+            Ok((user.expires_at > Utc::now().timestamp()).then(|| auth.clone()))
+        }
+        Auth::ApiKey(api_key) => Ok(ApiKey::find_active(pool, &api_key.id)
+            .await?
+            .map(|api_key| Auth::ApiKey(Box::new(api_key)))),
+    }
+}
+
+/// Identifies the user, superuser or API key behind credentials.
+fn identity(auth: &Auth) -> String {
+    match auth {
+        Auth::User(user) => format!("users/{}", user.id),
+        Auth::Superuser(superuser) => format!("_superusers/{}", superuser.id),
+        Auth::ApiKey(api_key) => format!("api_keys/{}", api_key.id),
+    }
+}
+
+async fn can_view(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    collection: &str,
+    record: &Map<String, Value>,
+    filter: Option<&str>,
+) -> Result<bool, Error> {
+    match collection {
+        "users" => record::can_view::<User>(conn, auth, record, filter).await,
+        "categories" => record::can_view::<Category>(conn, auth, record, filter).await,
+        "stores" => record::can_view::<Store>(conn, auth, record, filter).await,
+        "products" => record::can_view::<Product>(conn, auth, record, filter).await,
+        "orders" => record::can_view::<Order>(conn, auth, record, filter).await,
+        _ => Ok(false),
+    }
+}
+
+/// Checks a subscription filter against the columns of its collection.
+pub async fn check_filter(conn: &mut SqliteConnection, collection: &str, filter: &str) -> Result<(), Error> {
+    match collection {
+        "users" => record::check_filter::<User>(conn, filter).await,
+        "categories" => record::check_filter::<Category>(conn, filter).await,
+        "stores" => record::check_filter::<Store>(conn, filter).await,
+        "products" => record::check_filter::<Product>(conn, filter).await,
+        "orders" => record::check_filter::<Order>(conn, filter).await,
+        _ => Err(Error::not_found()),
+    }
+}
+
+/// Records referenced by the comma separated relation fields of `expand`
+/// that `auth` may view.
+async fn expand_relations(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    collection: &str,
+    record: &Map<String, Value>,
+    expand: &str,
+) -> Result<Map<String, Value>, Error> {
+    let relations: HashMap<String, String> =
+        sqlx::query_as(r#"SELECT "from", "table" FROM pragma_foreign_key_list(?)"#)
+            .bind(collection)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+
+    let mut expanded = Map::new();
+    for field in expand.split(',').map(str::trim) {
+        let (Some(parent), Some(id)) = (relations.get(field), record.get(field).and_then(Value::as_str)) else {
+            continue;
+        };
+        let related = match parent.as_str() {
+            "users" => view::<User>(conn, auth, id).await,
+            "categories" => view::<Category>(conn, auth, id).await,
+            "stores" => view::<Store>(conn, auth, id).await,
+            "products" => view::<Product>(conn, auth, id).await,
+            "orders" => view::<Order>(conn, auth, id).await,
+            _ => None,
+        };
+        if let Some(related) = related {
+            expanded.insert(field.to_string(), related);
+        }
+    }
+
+    Ok(expanded)
+}
+
+async fn view<T: Record>(conn: &mut SqliteConnection, auth: Option<&Auth>, id: &str) -> Option<Value> {
+    record::view::<T>(conn, auth, id).await.ok().map(|record| json!(record))
+}
@@ -0,0 +1,762 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use sqlx::sqlite::{SqliteArguments, SqliteRow};
+use sqlx::{Connection, FromRow, Row, Sqlite, SqliteConnection, Transaction};
+use validator::Validate;
+
+use crate::errors::{is_foreign_key_violation, BadRequest, Conflict, Error};
+use crate::forms::record::RecordData;
+use crate::models::api_key::ApiKeyScope;
+use crate::models::file::{self, FileField, STORAGE};
+use crate::models::filter;
+use crate::models::pocketbase::{calculate_total_pages, current_timestamp, generate_id, validate_id, PBListResponse};
+use crate::utils::auth::Auth;
+
+/// Number of ids tried when a generated one is already in use.
+pub const ID_ATTEMPTS: usize = 3;
+
+/// Fields managed by the server, ignored when sent by clients.
+const SYSTEM_FIELDS: [&str; 5] = ["id", "created", "updated", "collection_id", "collection_name"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    List,
+    View,
+    Create,
+    Update,
+    Delete,
+}
+
+/// PocketBase style API rules. `None` restricts the action to superusers,
+/// `Some("")` makes it public and any other value is an SQL condition on the
+/// record's columns that must hold. `@request.auth.id` is replaced by the id
+/// of the authenticated user and `@request.auth.store` by the store of the
+/// API key used, or `NULL` when absent.
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    pub list: Option<&'static str>,
+    pub view: Option<&'static str>,
+    pub create: Option<&'static str>,
+    pub update: Option<&'static str>,
+    pub delete: Option<&'static str>,
+}
+
+impl Rules {
+    pub fn get(&self, action: Action) -> Option<&'static str> {
+        match action {
+            Action::List => self.list,
+            Action::View => self.view,
+            Action::Create => self.create,
+            Action::Update => self.update,
+            Action::Delete => self.delete,
+        }
+    }
+}
+
+/// A row of a collection table. Field names must match the table columns.
+pub trait Record:
+    Serialize + DeserializeOwned + Validate + for<'r> FromRow<'r, SqliteRow> + Send + Unpin + 'static
+{
+    /// Collection and table name.
+    const COLLECTION: &'static str;
+    const RULES: Rules;
+    /// Columns of the table that are not exposed, and so cannot be filtered on.
+    const HIDDEN_COLUMNS: &'static [&'static str] = &[];
+    /// Columns holding the names of uploaded files.
+    const FILE_FIELDS: &'static [FileField] = &[];
+    /// Columns only set by dedicated endpoints, ignored when sent by clients
+    /// to the record API.
+    const MANAGED_FIELDS: &'static [&'static str] = &[];
+
+    fn id(&self) -> &str;
+
+    /// Scope an API key needs to perform `action`. API keys are rejected by
+    /// collections that return `None`.
+    fn api_key_scope(_action: Action) -> Option<ApiKeyScope> {
+        None
+    }
+}
+
+/// Returns the SQL condition the records must satisfy for `auth` to perform
+/// `action`, or `None` if there is no restriction.
+fn rule_condition<T: Record>(action: Action, auth: Option<&Auth>) -> Result<Option<String>, Error> {
+    // Superusers bypass all rules, including locked ones.
+    if auth.is_some_and(Auth::is_superuser) {
+        return Ok(None);
+    }
+
+    if let Some(api_key) = auth.and_then(Auth::api_key) {
+        let allowed = T::api_key_scope(action).is_some_and(|scope| api_key.has_scope(scope));
+        if !allowed {
+            return Err(Error::forbidden());
+        }
+    }
+
+    match T::RULES.get(action) {
+        None => Err(Error::forbidden()),
+        Some("") => Ok(None),
+        Some(rule) => {
+            let user_id = auth.and_then(Auth::user).map(|user| user.id.as_str());
+            let store = auth.and_then(Auth::api_key).map(|api_key| api_key.store.as_str());
+
+            Ok(Some(
+                rule.replace("@request.auth.id", &sql_literal(user_id))
+                    .replace("@request.auth.store", &sql_literal(store)),
+            ))
+        }
+    }
+}
+
+fn sql_literal(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value.replace('\'', "''")),
+        None => "NULL".to_string(),
+    }
+}
+
+/// Turns a PocketBase sort expression (`-created,name`) into an `ORDER BY`
+/// clause.
+fn order_by(sort: Option<&str>) -> Result<String, Error> {
+    let Some(sort) = sort.filter(|sort| !sort.trim().is_empty()) else {
+        return Ok("ORDER BY rowid".to_string());
+    };
+
+    let terms = sort
+        .split(',')
+        .map(|term| {
+            let term = term.trim();
+            let (column, direction) = match term.strip_prefix('-') {
+                Some(column) => (column, "DESC"),
+                None => (term.strip_prefix('+').unwrap_or(term), "ASC"),
+            };
+            if !is_identifier(column) {
+                return Err(BadRequest::new(format!("Invalid sort field {column:?}.")).into());
+            }
+            Ok(format!("\"{column}\" {direction}"))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(format!("ORDER BY {}", terms.join(", ")))
+}
+
+pub fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn bind_value<'q>(
+    query: sqlx::query::Query<'q, sqlx::Sqlite, SqliteArguments<'q>>,
+    value: &Value,
+) -> sqlx::query::Query<'q, sqlx::Sqlite, SqliteArguments<'q>> {
+    match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(value) => query.bind(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => query.bind(value),
+            None => query.bind(number.as_f64()),
+        },
+        Value::String(value) => query.bind(value.clone()),
+        other => query.bind(other.to_string()),
+    }
+}
+
+fn to_map<T: Record>(record: &T) -> Result<Map<String, Value>, Error> {
+    match serde_json::to_value(record).map_err(anyhow::Error::from)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(Error::InternalServerError),
+    }
+}
+
+async fn write<T: Record>(conn: &mut SqliteConnection, record: &T, insert: bool) -> Result<(), Error> {
+    let map = to_map(record)?;
+    let columns: Vec<&String> = map.keys().collect();
+
+    let sql = if insert {
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            T::COLLECTION,
+            columns
+                .iter()
+                .map(|column| format!("\"{column}\""))
+                .collect::<Vec<_>>()
+                .join(", "),
+            vec!["?"; columns.len()].join(", "),
+        )
+    } else {
+        format!(
+            "UPDATE {} SET {} WHERE id = ?",
+            T::COLLECTION,
+            columns
+                .iter()
+                .map(|column| format!("\"{column}\" = ?"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    };
+
+    let mut query = sqlx::query(&sql);
+    for value in map.values() {
+        query = bind_value(query, value);
+    }
+    if !insert {
+        query = query.bind(record.id().to_string());
+    }
+    match query.execute(&mut *conn).await {
+        Ok(_) => Ok(()),
+        Err(err) if is_foreign_key_violation(&err) => {
+            let fields = missing_relations(conn, T::COLLECTION, &map).await?;
+            match fields.first() {
+                Some(field) => Err(Error::invalid_field(
+                    field,
+                    "validation_missing_rel_records",
+                    "Failed to find all relation records with the provided ids.",
+                )),
+                None => Err(err.into()),
+            }
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Foreign key columns of `table` whose value in `map` does not match any
+/// row of the referenced table.
+pub async fn missing_relations(
+    conn: &mut SqliteConnection,
+    table: &str,
+    map: &Map<String, Value>,
+) -> Result<Vec<String>, Error> {
+    let foreign_keys: Vec<(String, String, Option<String>)> =
+        sqlx::query_as(r#"SELECT "from", "table", "to" FROM pragma_foreign_key_list(?)"#)
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut missing = Vec::new();
+    for (column, parent, parent_column) in foreign_keys {
+        let Some(value) = map.get(&column).filter(|value| !value.is_null()) else {
+            continue;
+        };
+        let parent_column = parent_column.unwrap_or_else(|| "id".to_string());
+        let sql = format!("SELECT EXISTS(SELECT 1 FROM \"{parent}\" WHERE \"{parent_column}\" = ?)");
+        let row = bind_value(sqlx::query(&sql), value).fetch_one(&mut *conn).await?;
+        let exists: bool = row.try_get(0)?;
+        if !exists {
+            missing.push(column);
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Tables holding rows that reference the `table` row `id` through a foreign
+/// key that prevents its deletion.
+async fn referencing_tables(
+    conn: &mut SqliteConnection,
+    table: &str,
+    id: &str,
+) -> Result<Vec<String>, Error> {
+    let references: Vec<(String, String)> = sqlx::query_as(
+        r#"SELECT m.name, f."from" FROM sqlite_master m JOIN pragma_foreign_key_list(m.name) f
+           WHERE m.type = 'table' AND f."table" = ? AND f.on_delete IN ('RESTRICT', 'NO ACTION')"#,
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut tables = Vec::new();
+    for (child, column) in references {
+        let exists: bool = sqlx::query_scalar(&format!(
+            "SELECT EXISTS(SELECT 1 FROM \"{child}\" WHERE \"{column}\" = ?)"
+        ))
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if exists && !tables.contains(&child) {
+            tables.push(child);
+        }
+    }
+
+    Ok(tables)
+}
+
+async fn satisfies(
+    conn: &mut SqliteConnection,
+    collection: &str,
+    id: &str,
+    condition: &str,
+) -> Result<bool, Error> {
+    let sql = format!("SELECT EXISTS(SELECT 1 FROM {collection} WHERE id = ? AND ({condition}))");
+    let exists: bool = sqlx::query_scalar(&sql)
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(exists)
+}
+
+/// Whether `err` reports an insert with an id that is already in use.
+pub fn is_id_taken(err: &Error) -> bool {
+    err.data()
+        .get("id")
+        .and_then(|field| field.get("code"))
+        .is_some_and(|code| code == "validation_not_unique")
+}
+
+pub fn invalid_formatting() -> Error {
+    BadRequest::new("Failed to load the submitted data due to invalid formatting.").into()
+}
+
+/// Removes the fields clients are not allowed to set.
+fn client_data(data: Value, keep_id: bool) -> Result<Map<String, Value>, Error> {
+    let Value::Object(mut data) = data else {
+        return Err(invalid_formatting());
+    };
+    for field in SYSTEM_FIELDS {
+        if !(keep_id && field == "id") {
+            data.remove(field);
+        }
+    }
+
+    Ok(data)
+}
+
+/// Converts a value sent as text, e.g. a CSV cell or a multipart form field,
+/// according to the declared type of its column. Empty values are `NULL`.
+pub fn text_value(text: &str, column_type: &str) -> Value {
+    if text.is_empty() {
+        return Value::Null;
+    }
+
+    let value = match column_type.to_ascii_uppercase().as_str() {
+        "INTEGER" => text.parse::<i64>().ok().map(Value::from),
+        "REAL" => text.parse::<f64>().ok().map(Value::from),
+        "BOOLEAN" => match text.to_ascii_lowercase().as_str() {
+            "true" | "1" => Some(Value::Bool(true)),
+            "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    // Left as text for the validation to report
+    value.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Client data with the fields sent as multipart text converted to the type
+/// of their column.
+pub async fn form_values<T: Record>(
+    conn: &mut SqliteConnection,
+    data: RecordData,
+    keep_id: bool,
+) -> Result<(Map<String, Value>, Vec<file::UploadedFile>), Error> {
+    let mut values = client_data(data.data, keep_id)?;
+    if !data.text_fields.is_empty() {
+        let columns = filter_columns::<T>(conn).await?;
+        for field in &data.text_fields {
+            let Some(column_type) = columns.get(field) else {
+                continue;
+            };
+            if let Some(value) = values.get_mut(field) {
+                if let Value::String(text) = value {
+                    *value = text_value(text, column_type);
+                }
+            }
+        }
+    }
+
+    Ok((values, data.files))
+}
+
+/// Columns of `T` that can be filtered on, with their declared type.
+async fn filter_columns<T: Record>(conn: &mut SqliteConnection) -> Result<HashMap<String, String>, Error> {
+    let columns = sqlx::query_as("SELECT name, type FROM pragma_table_info(?)")
+        .bind(T::COLLECTION)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .filter(|(name, _): &(String, String)| !T::HIDDEN_COLUMNS.contains(&name.as_str()))
+        .collect();
+
+    Ok(columns)
+}
+
+/// Checks that `filter` is valid for `T`.
+pub async fn check_filter<T: Record>(conn: &mut SqliteConnection, filter: &str) -> Result<(), Error> {
+    filter::parse(filter, &filter_columns::<T>(conn).await?)?;
+    Ok(())
+}
+
+/// Whether `auth` may view `record` and it matches `filter`. The record is a
+/// snapshot of a `T`, which may since have been deleted, so the conditions
+/// are evaluated against its values rather than the table.
+pub async fn can_view<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    record: &Map<String, Value>,
+    filter: Option<&str>,
+) -> Result<bool, Error> {
+    let Ok(condition) = rule_condition::<T>(Action::View, auth) else {
+        return Ok(false);
+    };
+    let columns = filter_columns::<T>(conn).await?;
+    let filter = filter
+        .filter(|filter| !filter.trim().is_empty())
+        .map(|filter| filter::parse(filter, &columns))
+        .transpose()?;
+    if condition.is_none() && filter.is_none() {
+        return Ok(true);
+    }
+
+    let values: Vec<(&String, &Value)> = record
+        .iter()
+        .filter(|(column, _)| columns.contains_key(column.as_str()))
+        .collect();
+    let select: Vec<String> = values.iter().map(|(column, _)| format!("? AS \"{column}\"")).collect();
+    let conditions: Vec<String> = condition
+        .into_iter()
+        .chain(filter.as_ref().map(|filter| filter.sql.clone()))
+        .map(|condition| format!("({condition})"))
+        .collect();
+    let sql = format!(
+        "SELECT EXISTS(SELECT 1 FROM (SELECT {}) WHERE {})",
+        select.join(", "),
+        conditions.join(" AND ")
+    );
+
+    let mut query = sqlx::query(&sql);
+    for (_, value) in values {
+        query = bind_value(query, value);
+    }
+    for param in filter.iter().flat_map(|filter| &filter.params) {
+        query = bind_value(query, param);
+    }
+    let visible: bool = query.fetch_one(&mut *conn).await?.try_get(0)?;
+
+    Ok(visible)
+}
+
+pub async fn list<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    page: i32,
+    per_page: i32,
+    sort: Option<&str>,
+    filter: Option<&str>,
+) -> Result<PBListResponse<T>, Error> {
+    let page = page.max(1);
+    let per_page = per_page.clamp(1, 500);
+    let mut conditions: Vec<String> = rule_condition::<T>(Action::List, auth)?.into_iter().collect();
+    let mut params = Vec::new();
+    if let Some(filter) = filter.filter(|filter| !filter.trim().is_empty()) {
+        let filter = filter::parse(filter, &filter_columns::<T>(conn).await?)?;
+        conditions.push(filter.sql);
+        params = filter.params;
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        let conditions: Vec<String> = conditions.iter().map(|condition| format!("({condition})")).collect();
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let order_by = order_by(sort)?;
+
+    let sql = format!("SELECT COUNT(*) FROM {} {where_clause}", T::COLLECTION);
+    let mut query = sqlx::query(&sql);
+    for param in &params {
+        query = bind_value(query, param);
+    }
+    let total_items: i64 = query.fetch_one(&mut *conn).await?.try_get(0)?;
+
+    let sql = format!("SELECT * FROM {} {where_clause} {order_by} LIMIT ? OFFSET ?", T::COLLECTION);
+    let mut query = sqlx::query(&sql);
+    for param in &params {
+        query = bind_value(query, param);
+    }
+    let items = query
+        .bind(per_page)
+        .bind((page - 1) * per_page)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(T::from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let total_items = total_items as i32;
+    Ok(PBListResponse {
+        page,
+        per_page,
+        total_items,
+        total_pages: calculate_total_pages(total_items, per_page),
+        items,
+    })
+}
+
+pub async fn find<T: Record>(conn: &mut SqliteConnection, id: &str) -> Result<Option<T>, Error> {
+    let record = sqlx::query_as::<_, T>(&format!("SELECT * FROM {} WHERE id = ?", T::COLLECTION))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(record)
+}
+
+pub async fn view<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+) -> Result<T, Error> {
+    let condition = rule_condition::<T>(Action::View, auth)?;
+    let sql = match condition {
+        Some(condition) => format!("SELECT * FROM {} WHERE id = ? AND ({condition})", T::COLLECTION),
+        None => format!("SELECT * FROM {} WHERE id = ?", T::COLLECTION),
+    };
+
+    sqlx::query_as::<_, T>(&sql)
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(Error::not_found)
+}
+
+/// File changes of the records written in a transaction. Uploads are stored
+/// before the transaction is committed, while files are only deleted once
+/// it is, as the records would otherwise reference missing files after a
+/// rollback.
+#[derive(Default)]
+pub struct PendingFiles {
+    saved: Vec<(&'static str, String, file::FileChanges)>,
+    removed: Vec<(&'static str, String)>,
+}
+
+impl PendingFiles {
+    /// Deletes the files the committed records no longer reference.
+    pub async fn committed(self) {
+        for (collection, id, files) in &self.saved {
+            STORAGE.remove(collection, id, &files.removed).await;
+        }
+        for (collection, id) in &self.removed {
+            STORAGE.remove_record(collection, id).await;
+        }
+    }
+
+    /// Deletes the uploads of the rolled back records.
+    pub async fn rolled_back(self) {
+        for (collection, id, files) in &self.saved {
+            STORAGE.discard(collection, id, files).await;
+        }
+    }
+}
+
+/// Commits `tx` if `result` is a success, then carries out or discards the
+/// file changes accordingly.
+async fn finish<T>(tx: Transaction<'_, Sqlite>, files: PendingFiles, result: Result<T, Error>) -> Result<T, Error> {
+    let result = match result {
+        Ok(value) => tx.commit().await.map(|()| value).map_err(Error::from),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(_) => files.committed().await,
+        Err(_) => files.rolled_back().await,
+    }
+
+    result
+}
+
+/// Creates a record from client data, filling omitted fields with the
+/// record's defaults. A new id is generated unless the client chose one.
+/// The create rule is checked against the inserted row and the insert
+/// rolled back if it does not hold. Uploaded files are stored before the
+/// record is committed.
+pub async fn create<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    data: RecordData,
+) -> Result<T, Error> {
+    let mut tx = conn.begin().await?;
+    let mut files = PendingFiles::default();
+    let result = create_in::<T>(&mut tx, auth, data, &mut files).await;
+    finish(tx, files, result).await
+}
+
+/// Creates a record as part of the transaction `tx`, see `create`.
+pub async fn create_in<T: Record>(
+    tx: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    data: RecordData,
+    pending: &mut PendingFiles,
+) -> Result<T, Error> {
+    let condition = rule_condition::<T>(Action::Create, auth)?;
+    let (mut data, files) = form_values::<T>(tx, data, true).await?;
+    remove_managed_fields::<T>(&mut data);
+    insert::<T>(tx, data, files, condition, pending).await
+}
+
+/// Creates a record from data computed by the server rather than sent by a
+/// client, as part of the transaction `tx`: the create rule is not checked
+/// and managed fields are kept. Meant for endpoints doing their own checks,
+/// such as checkout.
+pub async fn create_trusted_in<T: Record>(
+    tx: &mut SqliteConnection,
+    data: Value,
+    pending: &mut PendingFiles,
+) -> Result<T, Error> {
+    let Value::Object(data) = data else {
+        return Err(invalid_formatting());
+    };
+    insert::<T>(tx, data, Vec::new(), None, pending).await
+}
+
+fn remove_managed_fields<T: Record>(data: &mut Map<String, Value>) {
+    for field in T::MANAGED_FIELDS {
+        data.remove(*field);
+    }
+}
+
+async fn insert<T: Record>(
+    tx: &mut SqliteConnection,
+    mut data: Map<String, Value>,
+    files: Vec<file::UploadedFile>,
+    condition: Option<String>,
+    pending: &mut PendingFiles,
+) -> Result<T, Error> {
+    let files = file::apply(T::FILE_FIELDS, &mut data, None, files)?;
+    let generated_id = match data.get("id") {
+        None | Some(Value::Null) => true,
+        Some(Value::String(id)) if id.is_empty() => true,
+        Some(Value::String(id)) => {
+            validate_id(id)?;
+            false
+        }
+        Some(_) => return Err(invalid_formatting()),
+    };
+
+    let mut attempts = 0;
+    let record = loop {
+        if generated_id {
+            data.insert("id".to_string(), Value::String(generate_id()));
+        }
+        let record: T = serde_json::from_value(Value::Object(data.clone())).map_err(|_| invalid_formatting())?;
+        record.validate()?;
+
+        attempts += 1;
+        match write(tx, &record, true).await {
+            Ok(()) => break record,
+            Err(err) if generated_id && attempts < ID_ATTEMPTS && is_id_taken(&err) => continue,
+            Err(err) => return Err(err),
+        }
+    };
+    if let Some(condition) = condition {
+        if !satisfies(tx, T::COLLECTION, record.id(), &condition).await? {
+            return Err(Error::forbidden());
+        }
+    }
+    STORAGE.save(T::COLLECTION, record.id(), &files).await?;
+    pending.saved.push((T::COLLECTION, record.id().to_string(), files));
+
+    Ok(record)
+}
+
+/// Applies client data on top of an existing record. The update rule must
+/// hold both before and after the change. Files the record no longer
+/// references are deleted once it is committed.
+pub async fn update<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+) -> Result<T, Error> {
+    let mut tx = conn.begin().await?;
+    let mut files = PendingFiles::default();
+    let result = update_in::<T>(&mut tx, auth, id, data, &mut files).await;
+    finish(tx, files, result).await
+}
+
+/// Updates a record as part of the transaction `tx`, see `update`.
+pub async fn update_in<T: Record>(
+    tx: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+    data: RecordData,
+    pending: &mut PendingFiles,
+) -> Result<T, Error> {
+    let condition = rule_condition::<T>(Action::Update, auth)?;
+    let (mut data, files) = form_values::<T>(tx, data, false).await?;
+    remove_managed_fields::<T>(&mut data);
+
+    let existing = find::<T>(tx, id).await?.ok_or_else(Error::not_found)?;
+    if let Some(condition) = &condition {
+        if !satisfies(tx, T::COLLECTION, id, condition).await? {
+            return Err(Error::forbidden());
+        }
+    }
+
+    let mut map = to_map(&existing)?;
+    let files = file::apply(T::FILE_FIELDS, &mut data, Some(&map), files)?;
+    map.extend(data);
+    map.insert("updated".to_string(), Value::String(current_timestamp()));
+    let record: T = serde_json::from_value(Value::Object(map)).map_err(|_| invalid_formatting())?;
+    record.validate()?;
+
+    write(tx, &record, false).await?;
+    if let Some(condition) = &condition {
+        if !satisfies(tx, T::COLLECTION, id, condition).await? {
+            return Err(Error::forbidden());
+        }
+    }
+    STORAGE.save(T::COLLECTION, id, &files).await?;
+    pending.saved.push((T::COLLECTION, id.to_string(), files));
+
+    Ok(record)
+}
+
+/// Deletes a record, along with its files, and returns it.
+pub async fn delete<T: Record>(
+    conn: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+) -> Result<T, Error> {
+    let mut tx = conn.begin().await?;
+    let mut files = PendingFiles::default();
+    let result = delete_in::<T>(&mut tx, auth, id, &mut files).await;
+    finish(tx, files, result).await
+}
+
+/// Deletes a record as part of the transaction `tx`, see `delete`.
+pub async fn delete_in<T: Record>(
+    tx: &mut SqliteConnection,
+    auth: Option<&Auth>,
+    id: &str,
+    pending: &mut PendingFiles,
+) -> Result<T, Error> {
+    let condition = rule_condition::<T>(Action::Delete, auth)?;
+
+    let record = find::<T>(tx, id).await?.ok_or_else(Error::not_found)?;
+    if let Some(condition) = &condition {
+        if !satisfies(tx, T::COLLECTION, id, condition).await? {
+            return Err(Error::forbidden());
+        }
+    }
+
+    let result = sqlx::query(&format!("DELETE FROM {} WHERE id = ?", T::COLLECTION))
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+    if let Err(err) = result {
+        if !is_foreign_key_violation(&err) {
+            return Err(err.into());
+        }
+        let tables = referencing_tables(tx, T::COLLECTION, id).await?;
+        if tables.is_empty() {
+            return Err(err.into());
+        }
+        return Err(Conflict::new(format!("The record is still referenced by {}.", tables.join(", "))).into());
+    }
+    pending.removed.push((T::COLLECTION, id.to_string()));
+
+    Ok(record)
+}
@@ -0,0 +1,457 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::errors::Error;
+use crate::models::pocketbase::generate_id;
+use crate::settings::{self, StorageDriver};
+
+/// Request headers passed on to the object storage when serving a file.
+const FORWARDED_HEADERS: &[header::HeaderName] = &[
+    header::RANGE,
+    header::IF_RANGE,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+];
+
+/// Response headers of the object storage passed on to the client.
+const RELAYED_HEADERS: &[header::HeaderName] = &[
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+/// Where uploaded files and their thumbnails are kept. Objects are addressed
+/// by `/` separated keys, e.g. `products/<record id>/<file name>`, made of
+/// plain names only.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Contents of an object, `None` if it does not exist.
+    async fn read(&self, key: &str) -> Result<Option<Bytes>, Error>;
+
+    async fn exists(&self, key: &str) -> Result<bool, Error>;
+
+    /// Creates or replaces an object. Readers never see a partial object.
+    async fn write(&self, key: &str, data: Bytes) -> Result<(), Error>;
+
+    /// Deletes an object. Deleting a missing object succeeds.
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+
+    /// Deletes all the objects whose key starts with `prefix`, a `/`
+    /// terminated key.
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), Error>;
+
+    /// Responds to a download `request` with an object, honoring range and
+    /// conditional requests. A missing object is a `404`.
+    async fn serve(&self, key: &str, request: Request) -> Result<Response, Error>;
+}
+
+/// The storage selected by the `storage.driver` setting.
+pub fn from_settings(settings: &settings::Storage) -> anyhow::Result<Box<dyn Storage>> {
+    Ok(match settings.driver {
+        StorageDriver::Local => Box::new(LocalStorage::new(&settings.dir)),
+        StorageDriver::S3 => Box::new(S3Storage::new(&settings.s3)?),
+    })
+}
+
+/// Objects stored as files under a directory of the local disk.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn read(&self, key: &str) -> Result<Option<Bytes>, Error> {
+        match tokio::fs::read(self.path(key)).await {
+            Ok(data) => Ok(Some(data.into())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(anyhow::Error::from(err).context(format!("Failed to read {key}")).into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(tokio::fs::try_exists(self.path(key)).await.map_err(anyhow::Error::from)?)
+    }
+
+    async fn write(&self, key: &str, data: Bytes) -> Result<(), Error> {
+        let path = self.path(key);
+        // Written aside first, so concurrent requests never serve a partial file
+        let partial = path.with_file_name(format!(".{}.tmp", generate_id()));
+        let written = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&partial, &data).await?;
+            tokio::fs::rename(&partial, &path).await
+        }
+        .await;
+        if let Err(err) = written {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(anyhow::Error::from(err).context(format!("Failed to write {key}")).into());
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match tokio::fs::remove_file(self.path(key)).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(anyhow::Error::from(err).context(format!("Failed to delete {key}")).into()),
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), Error> {
+        match tokio::fs::remove_dir_all(self.path(prefix.trim_end_matches('/'))).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(anyhow::Error::from(err).context(format!("Failed to delete {prefix}")).into()),
+        }
+    }
+
+    async fn serve(&self, key: &str, request: Request) -> Result<Response, Error> {
+        let Ok(response) = ServeFile::new(self.path(key)).oneshot(request).await;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::not_found());
+        }
+
+        Ok(response.map(Body::new).into_response())
+    }
+}
+
+/// Objects stored in a bucket of an S3 compatible service, with requests
+/// signed with AWS Signature Version 4.
+pub struct S3Storage {
+    client: reqwest::Client,
+    /// URL of the bucket, ending with a `/`.
+    bucket_url: Url,
+    signer: Signer,
+}
+
+impl S3Storage {
+    pub fn new(settings: &settings::S3) -> anyhow::Result<Self> {
+        if settings.bucket.is_empty() {
+            anyhow::bail!("storage.s3.bucket is required");
+        }
+        let mut bucket_url = Url::parse(&settings.endpoint)
+            .with_context(|| format!("Invalid storage.s3.endpoint {:?}", settings.endpoint))?;
+        if settings.force_path_style {
+            let path = format!("{}/{}/", bucket_url.path().trim_end_matches('/'), settings.bucket);
+            bucket_url.set_path(&path);
+        } else {
+            let host = bucket_url.host_str().context("storage.s3.endpoint has no host")?;
+            let host = format!("{}.{}", settings.bucket, host);
+            bucket_url.set_host(Some(&host))?;
+            if !bucket_url.path().ends_with('/') {
+                let path = format!("{}/", bucket_url.path());
+                bucket_url.set_path(&path);
+            }
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            bucket_url,
+            signer: Signer {
+                access_key: settings.access_key.clone(),
+                secret_key: settings.secret_key.clone(),
+                region: settings.region.clone(),
+            },
+        })
+    }
+
+    fn url(&self, key: &str) -> Url {
+        let mut url = self.bucket_url.clone();
+        let path = format!("{}{}", url.path(), uri_encode(key, false));
+        url.set_path(&path);
+        url
+    }
+
+    /// Sends a signed request. `headers` are sent along without being signed.
+    async fn send(
+        &self,
+        method: Method,
+        url: Url,
+        mut headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<reqwest::Response, Error> {
+        let payload_hash = hex(&Sha256::digest(&body));
+        let now = Utc::now();
+        let mut signed = BTreeMap::new();
+        signed.insert("host".to_string(), host(&url));
+        signed.insert("x-amz-content-sha256".to_string(), payload_hash.clone());
+        signed.insert("x-amz-date".to_string(), now.format("%Y%m%dT%H%M%SZ").to_string());
+        let authorization = self.signer.authorization(method.as_str(), &url, &signed, &payload_hash, now);
+
+        for (name, value) in signed.iter().filter(|(name, _)| name.as_str() != "host") {
+            headers.insert(
+                header::HeaderName::from_bytes(name.as_bytes()).map_err(anyhow::Error::from)?,
+                HeaderValue::from_str(value).map_err(anyhow::Error::from)?,
+            );
+        }
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization).map_err(anyhow::Error::from)?,
+        );
+
+        let response = self
+            .client
+            .request(method.clone(), url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send {method} request to the object storage"))?;
+        Ok(response)
+    }
+
+    /// Fails with the error returned by the service, unless `response` has
+    /// one of the `expected` statuses.
+    async fn check(response: reqwest::Response, key: &str, expected: &[StatusCode]) -> Result<reqwest::Response, Error> {
+        if expected.contains(&response.status()) {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(anyhow::anyhow!("Object storage request for {key} failed with {status}: {body}").into())
+    }
+
+    /// Keys of the objects starting with `prefix`, listed a page at a time.
+    async fn list(&self, prefix: &str, continuation: Option<&str>) -> Result<(Vec<String>, Option<String>), Error> {
+        static KEY: Lazy<Regex> = Lazy::new(|| Regex::new(r"<Key>([^<]*)</Key>").unwrap());
+        static NEXT: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"<NextContinuationToken>([^<]*)</NextContinuationToken>").unwrap());
+
+        let mut url = self.bucket_url.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("list-type", "2").append_pair("prefix", prefix);
+            if let Some(token) = continuation {
+                query.append_pair("continuation-token", token);
+            }
+        }
+        let response = self.send(Method::GET, url, HeaderMap::new(), Bytes::new()).await?;
+        let response = Self::check(response, prefix, &[StatusCode::OK]).await?;
+        let body = response.text().await.map_err(anyhow::Error::from)?;
+
+        let keys = KEY.captures_iter(&body).map(|captures| xml_unescape(&captures[1])).collect();
+        let next = NEXT.captures(&body).map(|captures| xml_unescape(&captures[1]));
+        Ok((keys, next))
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn read(&self, key: &str) -> Result<Option<Bytes>, Error> {
+        let response = self.send(Method::GET, self.url(key), HeaderMap::new(), Bytes::new()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = Self::check(response, key, &[StatusCode::OK]).await?;
+        Ok(Some(response.bytes().await.map_err(anyhow::Error::from)?))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        let response = self.send(Method::HEAD, self.url(key), HeaderMap::new(), Bytes::new()).await?;
+        let response = Self::check(response, key, &[StatusCode::OK, StatusCode::NOT_FOUND]).await?;
+        Ok(response.status() == StatusCode::OK)
+    }
+
+    async fn write(&self, key: &str, data: Bytes) -> Result<(), Error> {
+        let mut headers = HeaderMap::new();
+        let content_type = mime_guess::from_path(key).first_or_octet_stream();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type.as_ref()).map_err(anyhow::Error::from)?,
+        );
+        let response = self.send(Method::PUT, self.url(key), headers, data).await?;
+        Self::check(response, key, &[StatusCode::OK]).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let response = self.send(Method::DELETE, self.url(key), HeaderMap::new(), Bytes::new()).await?;
+        Self::check(response, key, &[StatusCode::OK, StatusCode::NO_CONTENT, StatusCode::NOT_FOUND]).await?;
+        Ok(())
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<(), Error> {
+        let mut continuation = None;
+        loop {
+            let (keys, next) = self.list(prefix, continuation.as_deref()).await?;
+            for key in keys {
+                self.delete(&key).await?;
+            }
+            match next {
+                Some(token) => continuation = Some(token),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    async fn serve(&self, key: &str, request: Request) -> Result<Response, Error> {
+        let mut headers = HeaderMap::new();
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = request.headers().get(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        let response = self.send(Method::GET, self.url(key), headers, Bytes::new()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::not_found());
+        }
+        let expected = [
+            StatusCode::OK,
+            StatusCode::PARTIAL_CONTENT,
+            StatusCode::NOT_MODIFIED,
+            StatusCode::RANGE_NOT_SATISFIABLE,
+        ];
+        let response = Self::check(response, key, &expected).await?;
+
+        let mut builder = Response::builder().status(response.status());
+        for name in RELAYED_HEADERS {
+            if let Some(value) = response.headers().get(name) {
+                builder = builder.header(name, value);
+            }
+        }
+        let response = builder
+            .body(Body::from_stream(response.bytes_stream()))
+            .map_err(anyhow::Error::from)?;
+        Ok(response)
+    }
+}
+
+/// Computes the `Authorization` header of AWS Signature Version 4 requests.
+pub struct Signer {
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: String,
+}
+
+impl Signer {
+    /// Signs a request to `url` with the given lowercase `headers`, which
+    /// must include `host` and `x-amz-date`.
+    pub fn authorization(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &BTreeMap<String, String>,
+        payload_hash: &str,
+        time: DateTime<Utc>,
+    ) -> String {
+        let mut query: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (uri_encode(&name, true), uri_encode(&value, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical_headers = headers.iter().fold(String::new(), |mut canonical, (name, value)| {
+            let _ = writeln!(canonical, "{name}:{}", value.trim());
+            canonical
+        });
+        let signed_headers = headers.keys().map(String::as_str).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{method}\n{}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+            url.path()
+        );
+
+        let date = time.format("%Y%m%d").to_string();
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{scope}\n{}",
+            time.format("%Y%m%dT%H%M%SZ"),
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        let key = hmac(&key, self.region.as_bytes());
+        let key = hmac(&key, b"s3");
+        let key = hmac(&key, b"aws4_request");
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope},SignedHeaders={signed_headers},Signature={signature}",
+            self.access_key
+        )
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Value of the `Host` header sent to `url`, with the port unless it is the
+/// default one of the scheme.
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, as signatures
+/// expect. `/` is kept in object keys.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
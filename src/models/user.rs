@@ -2,8 +2,12 @@ use chrono::{DateTime, Utc, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-use crate::errors::Error;
+use crate::errors::{AuthenticateError, Error};
+use crate::models::password::{Verification, PASSWORDS};
+use crate::models::pocketbase::current_timestamp;
+use sqlx::SqlitePool;
 use tokio::task;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, sqlx::FromRow)]
 pub struct User {
@@ -38,7 +42,9 @@ impl User {
     }
 
     pub fn is_password_match(&self, password: &str) -> bool {
-        bcrypt::verify(password, self.password.as_ref()).unwrap_or(false)
+        PASSWORDS
+            .verify(password, &self.password)
+            .is_ok_and(|verification| verification != Verification::Mismatch)
     }
 }
 
@@ -67,14 +73,74 @@ pub async fn hash_password<P>(password: P) -> Result<String, Error>
 where
     P: AsRef<str> + Send + 'static,
 {
-    // TODO: Hash password with salt.
-    // https://docs.rs/bcrypt/latest/bcrypt/fn.hash_with_salt.html
-    #[cfg(not(test))]
-    let cost = bcrypt::DEFAULT_COST;
-    #[cfg(test)]
-    let cost = 4;
-    task::spawn_blocking(move || bcrypt::hash(password.as_ref(), cost))
+    task::spawn_blocking(move || PASSWORDS.hash(password.as_ref()))
         .await
         .map_err(Error::RunSyncTask)?
         .map_err(Error::HashPassword)
 }
+
+pub async fn verify_password<P, H>(password: P, hash: H) -> Result<Verification, Error>
+where
+    P: AsRef<str> + Send + 'static,
+    H: AsRef<str> + Send + 'static,
+{
+    task::spawn_blocking(move || PASSWORDS.verify(password.as_ref(), hash.as_ref()))
+        .await
+        .map_err(Error::RunSyncTask)?
+        .map_err(Error::HashPassword)
+}
+
+/// Checks `password` against the `(id, password hash)` credentials loaded
+/// from `table`, upgrading the stored hash if it is outdated. Missing
+/// credentials or an empty hash never match.
+pub async fn check_password(
+    pool: &SqlitePool,
+    table: &str,
+    credentials: Option<(&str, &str)>,
+    password: String,
+) -> Result<(), Error> {
+    let Some((id, password_hash)) = credentials.filter(|(_, hash)| !hash.is_empty()) else {
+        // Take as long as a real verification so response times don't reveal
+        // which accounts exist.
+        let _ = task::spawn_blocking(move || PASSWORDS.verify_dummy(&password)).await;
+        return Err(AuthenticateError::WrongCredentials.into());
+    };
+
+    let verification = verify_password(password.clone(), password_hash.to_string()).await?;
+    match verification {
+        Verification::Mismatch => Err(AuthenticateError::WrongCredentials.into()),
+        Verification::Match => Ok(()),
+        Verification::MatchNeedsRehash => {
+            rehash_password(pool, table, id, password, password_hash).await;
+            Ok(())
+        }
+    }
+}
+
+/// Replaces an outdated password hash after a successful login. Failures are
+/// logged and otherwise ignored, the user is authenticated either way.
+async fn rehash_password(pool: &SqlitePool, table: &str, id: &str, password: String, old_hash: &str) {
+    let new_hash = match hash_password(password).await {
+        Ok(hash) => hash,
+        Err(err) => {
+            warn!("Failed to rehash password for {} {}: {}", table, id, err);
+            return;
+        }
+    };
+
+    // Only replace the hash we verified against, in case the password was
+    // changed concurrently.
+    let sql = format!("UPDATE {table} SET password_hash = ?, updated = ? WHERE id = ? AND password_hash = ?");
+    let result = sqlx::query(&sql)
+        .bind(&new_hash)
+        .bind(current_timestamp())
+        .bind(id)
+        .bind(old_hash)
+        .execute(pool)
+        .await;
+
+    match result {
+        Ok(_) => info!("Upgraded password hash for {} {}", table, id),
+        Err(err) => warn!("Failed to store rehashed password for {} {}: {}", table, id, err),
+    }
+}
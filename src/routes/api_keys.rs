@@ -0,0 +1,14 @@
+use axum::{
+    routing::{delete, get},
+    Router,
+};
+use sqlx::SqlitePool;
+
+use crate::controllers::api_keys;
+
+pub fn create_route(pool: SqlitePool) -> Router {
+    Router::new()
+        .route("/api/stores/:store/api-keys", get(api_keys::list).post(api_keys::create))
+        .route("/api/stores/:store/api-keys/:id", delete(api_keys::revoke))
+        .with_state(pool)
+}
@@ -0,0 +1,12 @@
+use axum::{routing::post, Extension, Router};
+use sqlx::SqlitePool;
+
+use crate::controllers::batch;
+use crate::models::realtime::Realtime;
+
+pub fn create_route(pool: SqlitePool, realtime: Realtime) -> Router {
+    Router::new()
+        .route("/api/batch", post(batch::execute))
+        .layer(Extension(realtime))
+        .with_state(pool)
+}
@@ -0,0 +1,19 @@
+use axum::{
+    routing::{get, patch, post},
+    Extension, Router,
+};
+use sqlx::SqlitePool;
+
+use crate::controllers::carts;
+use crate::models::realtime::Realtime;
+
+pub fn create_route(pool: SqlitePool, realtime: Realtime) -> Router {
+    Router::new()
+        .route("/api/cart", get(carts::view))
+        .route("/api/cart/items", post(carts::add_item))
+        .route("/api/cart/items/:id", patch(carts::update_item).delete(carts::remove_item))
+        .route("/api/carts/:id/summary", get(carts::summary))
+        .route("/api/carts/:id/checkout", post(carts::checkout))
+        .layer(Extension(realtime))
+        .with_state(pool)
+}
@@ -0,0 +1,16 @@
+use axum::{
+    routing::{get, post},
+    Extension, Router,
+};
+use sqlx::SqlitePool;
+
+use crate::controllers::files;
+use crate::db::ReadPool;
+
+pub fn create_route(pool: SqlitePool, read_pool: ReadPool) -> Router {
+    Router::new()
+        .route("/api/files/token", post(files::token))
+        .route("/api/files/:collection/:record_id/:filename", get(files::serve))
+        .layer(Extension(read_pool))
+        .with_state(pool)
+}
@@ -1,2 +1,8 @@
+pub mod api_keys;
+pub mod batch;
+pub mod carts;
+pub mod files;
+pub mod orders;
 pub mod pocketbase;
+pub mod realtime;
 pub mod status;
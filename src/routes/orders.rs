@@ -0,0 +1,16 @@
+use axum::{
+    routing::{get, post},
+    Extension, Router,
+};
+use sqlx::SqlitePool;
+
+use crate::controllers::orders;
+use crate::models::realtime::Realtime;
+
+pub fn create_route(pool: SqlitePool, realtime: Realtime) -> Router {
+    Router::new()
+        .route("/api/orders/:id/status", post(orders::change_status))
+        .route("/api/orders/:id/history", get(orders::history))
+        .layer(Extension(realtime))
+        .with_state(pool)
+}
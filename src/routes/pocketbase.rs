@@ -1,17 +1,57 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{delete, get, patch, post},
-    Router,
+    Extension, Router,
 };
 use sqlx::SqlitePool;
 
 use crate::controllers::pocketbase::{
     health, list_records, get_record, create_record, update_record, delete_record
 };
+use crate::controllers::{superusers, users};
+use crate::db::ReadPool;
+use crate::models::file::max_upload_size;
+use crate::models::pocketbase::{Address, Cart, CartItem, Category, Customer, Order, Product, Store, User};
+use crate::models::realtime::Realtime;
+use crate::models::record::Record;
 
-pub fn create_route(pool: SqlitePool) -> Router {
+/// Room for the other fields and the multipart encoding of a record.
+const BODY_HEADROOM: usize = 1024 * 1024;
+
+/// Request body limit of the record routes: every file the largest record
+/// can hold, uploaded at once, and its other fields.
+const MAX_RECORD_BODY_SIZE: usize = max_upload_size(&[
+    User::FILE_FIELDS,
+    Category::FILE_FIELDS,
+    Store::FILE_FIELDS,
+    Product::FILE_FIELDS,
+    Cart::FILE_FIELDS,
+    CartItem::FILE_FIELDS,
+    Address::FILE_FIELDS,
+    Order::FILE_FIELDS,
+    Customer::FILE_FIELDS,
+]) + BODY_HEADROOM;
+
+pub fn create_route(pool: SqlitePool, read_pool: ReadPool, realtime: Realtime) -> Router {
     Router::new()
         .route("/api/health", get(health))
-        .route("/api/collections/:collection/records", get(list_records).post(create_record))
-        .route("/api/collections/:collection/records/:id", get(get_record).patch(update_record).delete(delete_record))
+        .route("/api/collections/users/auth-with-password", post(users::auth_with_password))
+        .route("/api/collections/users/impersonate/:id", post(superusers::impersonate))
+        .route("/api/collections/_superusers/auth-with-password", post(superusers::auth_with_password))
+        .route(
+            "/api/collections/:collection/records",
+            get(list_records)
+                .post(create_record)
+                .layer(DefaultBodyLimit::max(MAX_RECORD_BODY_SIZE)),
+        )
+        .route(
+            "/api/collections/:collection/records/:id",
+            get(get_record)
+                .patch(update_record)
+                .delete(delete_record)
+                .layer(DefaultBodyLimit::max(MAX_RECORD_BODY_SIZE)),
+        )
+        .layer(Extension(read_pool))
+        .layer(Extension(realtime))
         .with_state(pool)
 }
\ No newline at end of file
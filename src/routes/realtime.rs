@@ -0,0 +1,13 @@
+use axum::{routing::get, Extension, Router};
+use sqlx::SqlitePool;
+
+use crate::controllers::realtime;
+use crate::models::realtime::Realtime;
+
+pub fn create_route(pool: SqlitePool, realtime: Realtime) -> Router {
+    Router::new()
+        .route("/api/realtime", get(realtime::connect).post(realtime::set_subscriptions))
+        .route("/api/realtime/ws", get(realtime::websocket))
+        .layer(Extension(realtime))
+        .with_state(pool)
+}
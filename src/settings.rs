@@ -12,9 +12,213 @@ pub struct Server {
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+/// Pragmas applied to every SQLite connection. Foreign keys are always
+/// enabled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Pragmas {
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+    /// How long a connection waits for a lock before failing with
+    /// `SQLITE_BUSY`.
+    pub busy_timeout_ms: u64,
+    /// Page cache size. Positive values are pages, negative values KiB.
+    pub cache_size: i64,
+}
+
+impl Default for Pragmas {
+    fn default() -> Self {
+        Self {
+            journal_mode: JournalMode::Wal,
+            // NORMAL is durable in WAL mode except for the last transactions
+            // before a power loss.
+            synchronous: Synchronous::Normal,
+            busy_timeout_ms: 5000,
+            cache_size: -16 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Database {
+    /// SQLite connection URI, e.g. `sqlite://db/pocketbase_schema.db` or
+    /// `sqlite::memory:`.
+    pub uri: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a request waits for a free connection before failing.
+    pub acquire_timeout_ms: u64,
+    /// Optional path to a read-only replica of the database, used to serve
+    /// record listings and views.
+    pub replica: Option<String>,
+    pub pragmas: Pragmas,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            uri: "sqlite://db/pocketbase_schema.db".to_string(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_ms: 30_000,
+            replica: None,
+            pragmas: Pragmas::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Logger {
+    pub level: String,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageDriver {
+    Local,
+    S3,
+}
+
+/// An S3 compatible object storage, e.g. AWS S3 or MinIO.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct S3 {
+    /// Base URL of the service, e.g. `https://s3.eu-west-1.amazonaws.com` or
+    /// `http://localhost:9000`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Addresses the bucket as `<endpoint>/<bucket>` rather than as a sub
+    /// domain of the endpoint, as MinIO expects.
+    pub force_path_style: bool,
+}
+
+impl Default for S3 {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            force_path_style: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Storage {
+    pub driver: StorageDriver,
+    /// Directory the local driver stores uploaded files in, one sub directory
+    /// per collection and record.
+    pub dir: String,
+    pub s3: S3,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            driver: StorageDriver::Local,
+            dir: "storage".to_string(),
+            s3: S3::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Auth {
     pub secret: String,
+    #[serde(default)]
+    pub password: Password,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Argon2 {
+    /// Memory cost in KiB.
+    pub memory_cost: u32,
+    /// Number of iterations.
+    pub time_cost: u32,
+    /// Degree of parallelism.
+    pub parallelism: u32,
+}
+
+impl Default for Argon2 {
+    fn default() -> Self {
+        // OWASP recommended minimum for argon2id.
+        Self {
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Password {
+    /// Algorithm used for new hashes. Hashes produced by the other algorithm
+    /// are still verified and upgraded on the next successful login.
+    pub algorithm: PasswordAlgorithm,
+    pub argon2: Argon2,
+    pub bcrypt_cost: u32,
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Optional path to a newline separated list of known breached passwords.
+    pub breached_list: Option<String>,
+}
+
+impl Default for Password {
+    fn default() -> Self {
+        Self {
+            algorithm: PasswordAlgorithm::Argon2id,
+            argon2: Argon2::default(),
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+            min_length: 8,
+            max_length: 128,
+            breached_list: None,
+        }
+    }
 }
 
 // Remove the #[allow(dead_code)] attribute from the Settings struct when all the fields are being
@@ -24,12 +228,20 @@ pub struct Auth {
 pub struct Settings {
     pub environment: String,
     pub server: Server,
-    
-    
+    #[serde(default)]
+    pub database: Database,
+    #[serde(default)]
+    pub logger: Logger,
+    #[serde(default)]
+    pub storage: Storage,
     pub auth: Auth,
 }
 
 impl Settings {
+    pub fn is_production(&self) -> bool {
+        self.environment == "production"
+    }
+
     pub fn new() -> Result<Self, ConfigError> {
         let run_mode = env::var("RUN_MODE").unwrap_or_else(|_| "development".into());
 
@@ -0,0 +1,124 @@
+use serde_json::{json, Value};
+
+use super::{login, sign_up, TestApp};
+
+async fn api_key_app() -> TestApp {
+    TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await
+}
+
+async fn create_key(url: &str, token: &str, scopes: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/stores/store_csv/api-keys"))
+        .bearer_auth(token)
+        .json(&json!({ "name": "Sync", "scopes": scopes }))
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn with_key(request: reqwest::RequestBuilder, key: &str) -> u16 {
+    request.header("x-api-key", key).send().await.unwrap().status().as_u16()
+}
+
+async fn last_used_at(app: &TestApp, id: &str) -> Option<String> {
+    sqlx::query_scalar("SELECT last_used_at FROM api_keys WHERE id = ?")
+        .bind(id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn api_keys_are_limited_to_their_store_and_scopes() {
+    let app = api_key_app().await;
+    let url = app.serve().await;
+    let owner = login(&url, "csv").await;
+
+    let (status, body) = create_key(&url, &owner, json!(["products:read", "products:write"])).await;
+    assert_eq!(status, 201, "{body}");
+    let key = body["key"].as_str().unwrap();
+    assert!(key.starts_with(&format!("vsk_{}_", body["prefix"].as_str().unwrap())));
+    let listed: Value = reqwest::Client::new()
+        .get(format!("{url}/api/stores/store_csv/api-keys"))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["items"][0]["id"], body["id"]);
+    assert!(listed["items"][0].get("key").is_none());
+    assert!(listed["items"][0].get("key_hash").is_none());
+
+    let client = reqwest::Client::new();
+    let products = format!("{url}/api/collections/products/records");
+    assert_eq!(with_key(client.get(&products), key).await, 200);
+    let status = client.get(&products).bearer_auth(key).send().await.unwrap().status();
+    assert_eq!(status, 200);
+    let patch = client
+        .patch(format!("{products}/prod_csv"))
+        .json(&json!({ "inventory": 3 }));
+    assert_eq!(with_key(patch, key).await, 200);
+    // Products of other stores are out of reach
+    let patch = client
+        .patch(format!("{products}/prod_book"))
+        .json(&json!({ "inventory": 3 }));
+    assert_eq!(with_key(patch, key).await, 403);
+    // Orders need the orders:read scope
+    let orders = client.get(format!("{url}/api/collections/orders/records"));
+    assert_eq!(with_key(orders, key).await, 403);
+
+    // Another secret under the same prefix is not accepted
+    let mut tampered = key.to_string();
+    let last = if tampered.ends_with('a') { "b" } else { "a" };
+    tampered.replace_range(tampered.len() - 1.., last);
+    assert_eq!(with_key(client.get(&products), &tampered).await, 401);
+
+    // Only the store owner manages its keys
+    let (_, stranger) = sign_up(&url, "stranger").await;
+    let (status, _) = create_key(&url, &stranger, json!(["orders:read"])).await;
+    assert_eq!(status, 403);
+    let revoke = format!("{url}/api/stores/store_csv/api-keys/{}", body["id"].as_str().unwrap());
+    let status = client.delete(&revoke).bearer_auth(&stranger).send().await.unwrap().status();
+    assert_eq!(status, 403);
+
+    let status = client.delete(&revoke).bearer_auth(&owner).send().await.unwrap().status();
+    assert_eq!(status, 204);
+    assert_eq!(with_key(client.get(&products), key).await, 401);
+    let status = client.delete(&revoke).bearer_auth(&owner).send().await.unwrap().status();
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn api_key_use_is_recorded_once_a_minute() {
+    let app = api_key_app().await;
+    let url = app.serve().await;
+    let owner = login(&url, "csv").await;
+    let (_, body) = create_key(&url, &owner, json!(["orders:read"])).await;
+    let (id, key) = (body["id"].as_str().unwrap(), body["key"].as_str().unwrap());
+    assert_eq!(last_used_at(&app, id).await, None);
+
+    let orders = format!("{url}/api/collections/orders/records");
+    let client = reqwest::Client::new();
+    assert_eq!(with_key(client.get(&orders), key).await, 200);
+    let first = last_used_at(&app, id).await.expect("the use of the key is not recorded");
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    assert_eq!(with_key(client.get(&orders), key).await, 200);
+    assert_eq!(last_used_at(&app, id).await.as_ref(), Some(&first));
+
+    sqlx::query("UPDATE api_keys SET last_used_at = '2020-01-01 00:00:00.000Z' WHERE id = ?")
+        .bind(id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(with_key(client.get(&orders), key).await, 200);
+    let last = last_used_at(&app, id).await.unwrap();
+    assert!(last >= first, "{last} < {first}");
+}
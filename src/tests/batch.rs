@@ -0,0 +1,178 @@
+use serde_json::{json, Value};
+
+use super::{login, sign_up, TestApp};
+
+async fn batch(url: &str, token: &str, requests: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/batch"))
+        .bearer_auth(token)
+        .json(&json!({ "requests": requests }))
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn batch_requests_are_committed_together() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let (user, token) = sign_up(&url, "batcher").await;
+
+    let product = json!({ "name": "Lamp", "price": "20", "category": "cat_csv", "store": "batchstore00001" });
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/stores/records",
+          "body": { "id": "batchstore00001", "name": "Batch store", "slug": "batch-store", "user": user } },
+        { "method": "POST", "url": "/api/collections/products/records",
+          "body": { "id": "batchlamp000001", "name": "Lamp", "price": "20", "category": "cat_csv", "store": "batchstore00001" } },
+        { "method": "PATCH", "url": "/api/collections/products/records/batchlamp000001", "body": { "inventory": 3 } },
+        { "method": "PUT", "url": "/api/collections/products/records",
+          "body": { "id": "batchlamp000001", "name": "Desk lamp" } },
+        { "method": "PUT", "url": "/api/collections/products/records?expand=store", "body": product },
+        { "method": "DELETE", "url": "/api/collections/products/records/batchlamp000001" },
+    ]);
+    let (status, body) = batch(&url, &token, requests).await;
+    assert_eq!(status, 200, "{body}");
+    let statuses: Vec<&Value> = body.as_array().unwrap().iter().map(|item| &item["status"]).collect();
+    assert_eq!(statuses, [200, 200, 200, 200, 200, 204]);
+    assert_eq!(body[2]["body"]["inventory"], 3);
+    assert_eq!(body[3]["body"]["name"], "Desk lamp");
+    assert_eq!(body[3]["body"]["inventory"], 3);
+    assert_eq!(body[5]["body"], Value::Null);
+
+    let products: Vec<String> = sqlx::query_scalar("SELECT id FROM products WHERE store = 'batchstore00001'")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(products, vec![body[4]["body"]["id"].as_str().unwrap().to_string()]);
+}
+
+#[tokio::test]
+async fn failed_batch_requests_roll_back_the_batch() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let (user, token) = sign_up(&url, "rollback").await;
+
+    // The store rule only allows creating stores of the authenticated user
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/stores/records",
+          "body": { "name": "Mine", "slug": "mine", "user": user } },
+        { "method": "PATCH", "url": "/api/collections/products/records/prod_csv", "body": { "inventory": 1 } },
+    ]);
+    let (status, body) = batch(&url, &token, requests).await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["message"], "Batch transaction failed.");
+    let failure = &body["data"]["requests"]["1"];
+    assert_eq!(failure["code"], "batch_request_failed");
+    assert_eq!(failure["response"]["status"], 403);
+
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/stores/records",
+          "body": { "name": "Mine", "slug": "mine", "user": user } },
+        { "method": "POST", "url": "/api/collections/stores/records",
+          "body": { "name": "Taken", "slug": "mine", "user": user } },
+    ]);
+    let (status, body) = batch(&url, &token, requests).await;
+    assert_eq!(status, 400, "{body}");
+    let response = &body["data"]["requests"]["1"]["response"];
+    assert_eq!(response["data"]["slug"]["code"], "validation_not_unique");
+
+    let stores: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stores WHERE user = ?")
+        .bind(&user)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(stores, 0);
+    let inventory: i64 = sqlx::query_scalar("SELECT inventory FROM products WHERE id = 'prod_csv'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(inventory, 12);
+
+    let requests = json!([
+        { "method": "GET", "url": "/api/collections/products/records" },
+    ]);
+    let (status, body) = batch(&url, &token, requests).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["requests"]["0"]["response"]["status"], 400);
+    let (status, _) = batch(&url, &token, json!([])).await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn batches_span_carts_addresses_and_customers() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let buyer = login(&url, "buyer").await;
+
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/addresses/records",
+          "body": { "id": "batchaddress001", "line1": "2 Side St", "city": "Hue", "state": "TTH",
+                    "postal_code": "530000", "country": "VN", "user": "user_buyer" } },
+        { "method": "POST", "url": "/api/collections/carts/records",
+          "body": { "id": "batchcart000001", "user": "user_buyer", "session_id": "someone-else" } },
+        { "method": "POST", "url": "/api/collections/cart_items/records",
+          "body": { "cart": "batchcart000001", "product": "prod_lamp", "quantity": 2 } },
+        { "method": "PATCH", "url": "/api/collections/cart_items/records/item_mug", "body": { "quantity": 3 } },
+        { "method": "DELETE", "url": "/api/collections/cart_items/records/item_lamp" },
+    ]);
+    let (status, body) = batch(&url, &buyer, requests).await;
+    assert_eq!(status, 200, "{body}");
+    let statuses: Vec<&Value> = body.as_array().unwrap().iter().map(|item| &item["status"]).collect();
+    assert_eq!(statuses, [200, 200, 200, 200, 204]);
+    assert_eq!(body[1]["body"]["session_id"], Value::Null);
+    assert_eq!(body[3]["body"]["quantity"], 3);
+    let items: Vec<(String, i64)> = sqlx::query_as("SELECT product, quantity FROM cart_items WHERE cart = 'batchcart000001'")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(items, vec![("prod_lamp".to_string(), 2)]);
+
+    // Inactive products cannot be put in a cart, and customers belong to
+    // store owners
+    for request in [
+        json!({ "method": "POST", "url": "/api/collections/cart_items/records",
+                "body": { "cart": "cart_buyer", "product": "prod_csv", "quantity": 1 } }),
+        json!({ "method": "POST", "url": "/api/collections/customers/records",
+                "body": { "store": "store_csv", "email": "buyer@example.com" } }),
+    ] {
+        let requests = json!([
+            { "method": "DELETE", "url": "/api/collections/addresses/records/batchaddress001" },
+            request,
+        ]);
+        let (status, body) = batch(&url, &buyer, requests).await;
+        assert_eq!(status, 400, "{body}");
+        assert_eq!(body["data"]["requests"]["1"]["response"]["status"], 403);
+    }
+    let addresses: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM addresses WHERE id = 'batchaddress001'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(addresses, 1);
+
+    let owner = login(&url, "csv").await;
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/customers/records",
+          "body": { "id": "batchcustomer01", "store": "store_csv", "email": "walk-in@example.com",
+                    "name": "Walk-in", "total_orders": 99 } },
+        { "method": "PATCH", "url": "/api/collections/cart_items/records/item_mug", "body": { "quantity": 1 } },
+    ]);
+    let (status, body) = batch(&url, &owner, requests).await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["data"]["requests"]["1"]["response"]["status"], 403);
+
+    let requests = json!([
+        { "method": "POST", "url": "/api/collections/customers/records",
+          "body": { "id": "batchcustomer01", "store": "store_csv", "email": "walk-in@example.com",
+                    "name": "Walk-in", "total_orders": 99 } },
+    ]);
+    let (status, body) = batch(&url, &owner, requests).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body[0]["body"]["name"], "Walk-in");
+    assert_eq!(body[0]["body"]["total_orders"], 0);
+}
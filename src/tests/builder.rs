@@ -0,0 +1,57 @@
+use serde_json::{json, Value};
+
+use super::TestApp;
+
+#[tokio::test]
+async fn health_check() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+
+    let response = reqwest::get(format!("{url}/api/health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[tokio::test]
+async fn apps_do_not_share_state() {
+    let user = json!({
+        "email": "isolated@example.com",
+        "username": "isolated",
+        "password": "correct horse battery staple",
+        "passwordConfirm": "correct horse battery staple",
+    });
+
+    for app in [TestApp::builder().build().await, TestApp::builder().temp_file().build().await] {
+        let url = app.serve().await;
+        let response = reqwest::Client::new()
+            .post(format!("{url}/api/collections/users/records"))
+            .json(&user)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+}
+
+#[tokio::test]
+async fn fixtures_are_loaded() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/categories.sql")
+        .build()
+        .await;
+    let url = app.serve().await;
+
+    let response = reqwest::get(format!("{url}/api/collections/categories/records/cat_fixture"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["name"], "Fixture");
+
+    let slug: String = sqlx::query_scalar("SELECT slug FROM categories WHERE id = 'cat_fixture'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(slug, "fixture");
+}
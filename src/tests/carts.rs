@@ -0,0 +1,227 @@
+use reqwest::{header, Method, RequestBuilder};
+use serde_json::{json, Value};
+
+use super::{login, sign_up, TestApp};
+
+async fn cart_app() -> TestApp {
+    TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await
+}
+
+/// Credentials of a cart request: a guest session cookie or a user token.
+enum As<'a> {
+    Guest(Option<&'a str>),
+    User(&'a str),
+}
+
+fn request(url: &str, method: Method, path: &str, credentials: &As) -> RequestBuilder {
+    let request = reqwest::Client::new().request(method, format!("{url}{path}"));
+    match credentials {
+        As::Guest(Some(cookie)) => request.header(header::COOKIE, *cookie),
+        As::Guest(None) => request,
+        As::User(token) => request.bearer_auth(token),
+    }
+}
+
+async fn add_item(url: &str, credentials: &As<'_>, product: &str, quantity: i64) -> (u16, Option<String>, Value) {
+    let response = request(url, Method::POST, "/api/cart/items", credentials)
+        .json(&json!({ "product": product, "quantity": quantity }))
+        .send()
+        .await
+        .unwrap();
+    let cookie = set_cookie(&response);
+    (response.status().as_u16(), cookie, response.json().await.unwrap())
+}
+
+async fn view_cart(url: &str, credentials: &As<'_>) -> (u16, Value) {
+    let response = request(url, Method::GET, "/api/cart", credentials).send().await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap_or(Value::Null))
+}
+
+/// The `name=value` pair of the cart session cookie set by a response.
+fn set_cookie(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::SET_COOKIE)
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap().to_string())
+}
+
+fn quantities(cart: &Value) -> Vec<(String, i64)> {
+    let mut quantities: Vec<(String, i64)> = cart["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| (item["product"].as_str().unwrap().to_string(), item["quantity"].as_i64().unwrap()))
+        .collect();
+    quantities.sort();
+    quantities
+}
+
+#[tokio::test]
+async fn guests_get_a_cart_session() {
+    let app = cart_app().await;
+    let url = app.serve().await;
+
+    let (status, _) = view_cart(&url, &As::Guest(None)).await;
+    assert_eq!(status, 404);
+    let (status, cookie, item) = add_item(&url, &As::Guest(None), "prod_lamp", 1).await;
+    assert_eq!(status, 200, "{item}");
+    let cookie = cookie.expect("no cart session cookie");
+    assert!(cookie.starts_with("vieshare_cart="));
+    let guest = As::Guest(Some(&cookie));
+
+    let (status, again, _) = add_item(&url, &guest, "prod_lamp", 2).await;
+    assert_eq!(status, 200);
+    assert_eq!(again, None);
+    add_item(&url, &guest, "prod_book", 1).await;
+    let (status, cart) = view_cart(&url, &guest).await;
+    assert_eq!(status, 200, "{cart}");
+    assert_eq!(cart["user"], Value::Null);
+    assert_eq!(quantities(&cart), [("prod_book".to_string(), 1), ("prod_lamp".to_string(), 3)]);
+
+    let (status, _, body) = add_item(&url, &guest, "prod_csv", 1).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["product"]["code"], "validation_product_inactive");
+
+    // Other sessions cannot change the items
+    let item = item["id"].as_str().unwrap();
+    let (_, other, _) = add_item(&url, &As::Guest(None), "prod_mug", 1).await;
+    let other = other.unwrap();
+    let response = request(&url, Method::PATCH, &format!("/api/cart/items/{item}"), &As::Guest(Some(&other)))
+        .json(&json!({ "quantity": 5 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = request(&url, Method::PATCH, &format!("/api/cart/items/{item}"), &guest)
+        .json(&json!({ "quantity": 5 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = request(&url, Method::DELETE, &format!("/api/cart/items/{item}"), &guest)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let (_, cart) = view_cart(&url, &guest).await;
+    assert_eq!(quantities(&cart), [("prod_book".to_string(), 1)]);
+}
+
+async fn login_with_cart(url: &str, identity: &str, cookie: &str) -> (String, Option<String>) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/auth-with-password"))
+        .header(header::COOKIE, cookie)
+        .json(&json!({ "identity": identity, "password": "correct horse battery staple" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let cookie = response.headers().get(header::SET_COOKIE).map(|value| value.to_str().unwrap().to_string());
+    let body: Value = response.json().await.unwrap();
+    (body["token"].as_str().unwrap().to_string(), cookie)
+}
+
+#[tokio::test]
+async fn guest_carts_are_merged_on_login() {
+    let app = cart_app().await;
+    let url = app.serve().await;
+    sqlx::query("UPDATE carts SET updated = '2030-01-01 00:00:00.000Z' WHERE id = 'cart_buyer'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let (_, cookie, _) = add_item(&url, &As::Guest(None), "prod_lamp", 1).await;
+    let cookie = cookie.unwrap();
+    add_item(&url, &As::Guest(Some(&cookie)), "prod_book", 2).await;
+
+    let (token, removed) = login_with_cart(&url, "buyer", &cookie).await;
+    let removed = removed.expect("the cart session cookie is not removed");
+    assert!(removed.starts_with("vieshare_cart=;"), "{removed}");
+    let (status, cart) = view_cart(&url, &As::User(&token)).await;
+    assert_eq!(status, 200, "{cart}");
+    assert_eq!(cart["id"], "cart_buyer");
+    assert_eq!(
+        quantities(&cart),
+        [("prod_book".to_string(), 2), ("prod_lamp".to_string(), 3), ("prod_mug".to_string(), 1)]
+    );
+    let (status, _) = view_cart(&url, &As::Guest(Some(&cookie))).await;
+    assert_eq!(status, 404);
+    let carts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM carts WHERE user IS NULL")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(carts, 0);
+
+    // Users without a cart take over the guest cart
+    let (user, _) = sign_up(&url, "newcomer").await;
+    let (_, cookie, _) = add_item(&url, &As::Guest(None), "prod_mug", 1).await;
+    let (token, _) = login_with_cart(&url, "newcomer", &cookie.unwrap()).await;
+    let (_, cart) = view_cart(&url, &As::User(&token)).await;
+    assert_eq!(cart["user"], user);
+    assert_eq!(quantities(&cart), [("prod_mug".to_string(), 1)]);
+
+    // Signing in without a guest cart leaves the user's cart alone
+    let token = login(&url, "buyer").await;
+    let (_, cart) = view_cart(&url, &As::User(&token)).await;
+    assert_eq!(cart["items"].as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn cart_summary_prices_the_items() {
+    let app = cart_app().await;
+    let url = app.serve().await;
+    sqlx::query("UPDATE cart_items SET quantity = 2 WHERE id = 'stores_mug'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE products SET images = '[\"lamp_k8w2qn0xz3.png\", \"lamp_side.png\"]' WHERE id = 'prod_lamp'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let token = login(&url, "buyer").await;
+
+    let response = request(&url, Method::GET, "/api/carts/cart_stores/summary", &As::User(&token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let summary: Value = response.json().await.unwrap();
+    assert_eq!(summary["total"], "33.20");
+    assert_eq!((&summary["count"], &summary["quantity"]), (&json!(3), &json!(5)));
+    assert_eq!(summary["orderable"], false);
+    assert_eq!(
+        summary["stores"],
+        json!([
+            { "store": "store_csv", "name": "CSV store", "count": 2, "quantity": 3, "subtotal": "18.70" },
+            { "store": "store_seller", "name": "Seller store", "count": 1, "quantity": 2, "subtotal": "14.50" },
+        ])
+    );
+    let lamp = &summary["items"][0];
+    assert_eq!(
+        (&lamp["name"], &lamp["image"], &lamp["price"], &lamp["subtotal"]),
+        (&json!("Lamp"), &json!("lamp_k8w2qn0xz3.png"), &json!("12.50"), &json!("12.50"))
+    );
+    assert_eq!(lamp["warning"], Value::Null);
+    assert_eq!(summary["items"][1]["image"], Value::Null);
+    assert_eq!(summary["items"][2]["warning"]["code"], "validation_out_of_stock");
+
+    // Carts of other users and sessions are not found
+    let (_, stranger) = sign_up(&url, "stranger").await;
+    let response = request(&url, Method::GET, "/api/carts/cart_stores/summary", &As::User(&stranger))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let (_, cookie, _) = add_item(&url, &As::Guest(None), "prod_lamp", 1).await;
+    let response = request(&url, Method::GET, "/api/carts/cart_stores/summary", &As::Guest(cookie.as_deref()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,273 @@
+use serde_json::{json, Value};
+
+use super::{login, sign_up, TestApp};
+
+async fn checkout_app() -> TestApp {
+    TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await
+}
+
+async fn checkout(url: &str, token: &str, cart: &str, form: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/carts/{cart}/checkout"))
+        .bearer_auth(token)
+        .json(&form)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn inventory(app: &TestApp, product: &str) -> i64 {
+    sqlx::query_scalar("SELECT inventory FROM products WHERE id = ?")
+        .bind(product)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn checkout_turns_the_cart_into_an_order() {
+    let app = checkout_app().await;
+    let url = app.serve().await;
+    let token = login(&url, "buyer").await;
+
+    let (status, body) = checkout(&url, &token, "cart_buyer", json!({ "address": "address_buyer", "notes": "Ring twice" })).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["amount"], "28.10");
+    assert_eq!(body["orders"].as_array().unwrap().len(), 1);
+    let order = &body["orders"][0];
+    assert_eq!(order["checkout"], body["id"]);
+    assert_eq!(order["user"], "user_buyer");
+    assert_eq!(order["store"], "store_csv");
+    assert_eq!(order["status"], "pending");
+    assert_eq!(order["amount"], "28.10");
+    assert_eq!(order["quantity"], 3);
+    assert_eq!(order["email"], "buyer@example.com");
+    assert_eq!(order["notes"], "Ring twice");
+    let items: Value = serde_json::from_str(order["items"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        items,
+        json!([
+            { "product": "prod_lamp", "name": "Lamp", "price": "12.50", "quantity": 2, "subtotal": "25.00" },
+            { "product": "prod_mug", "name": "Mug", "price": "3.10", "quantity": 1, "subtotal": "3.10" },
+        ])
+    );
+
+    assert_eq!(inventory(&app, "prod_lamp").await, 3);
+    assert_eq!(inventory(&app, "prod_mug").await, 0);
+    let items: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM cart_items WHERE cart = 'cart_buyer'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(items, 0);
+
+    let (status, body) = checkout(&url, &token, "cart_buyer", json!({ "address": "address_buyer" })).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["message"], "The cart is empty.");
+
+    sqlx::query("INSERT INTO cart_items (id, cart, product, quantity) VALUES ('item_again', 'cart_buyer', 'prod_lamp', 1)")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let (status, body) = checkout(&url, &token, "cart_buyer", json!({ "address": "address_buyer" })).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["orders"][0]["amount"], "12.50");
+
+    let customer: (i64, String) =
+        sqlx::query_as("SELECT total_orders, total_spent FROM customers WHERE store = 'store_csv' AND email = 'buyer@example.com'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(customer, (2, "40.60".to_string()));
+}
+
+#[tokio::test]
+async fn checkout_rejects_unavailable_products() {
+    let app = checkout_app().await;
+    let url = app.serve().await;
+    let token = login(&url, "buyer").await;
+
+    sqlx::query("UPDATE cart_items SET quantity = 2 WHERE id = 'item_mug'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO cart_items (id, cart, product, quantity) VALUES ('item_csv', 'cart_buyer', 'prod_csv', 1)")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let (status, body) = checkout(&url, &token, "cart_buyer", json!({ "address": "address_buyer" })).await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["data"]["items"]["item_mug"]["code"], "validation_out_of_stock");
+    assert_eq!(body["data"]["items"]["item_csv"]["code"], "validation_product_inactive");
+    assert!(body["data"]["items"].get("item_lamp").is_none());
+    assert_eq!(inventory(&app, "prod_lamp").await, 5);
+
+    let (status, body) = checkout(&url, &token, "cart_buyer", json!({ "address": "missing" })).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["address"]["code"], "validation_missing_rel_records");
+
+    // Carts of other users are not found
+    let (_, stranger) = sign_up(&url, "stranger").await;
+    let (status, _) = checkout(&url, &stranger, "cart_buyer", json!({ "address": "address_buyer" })).await;
+    assert_eq!(status, 404);
+
+    let orders: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(orders, 1);
+}
+
+async fn list_orders(url: &str, token: &str, filter: &str) -> Vec<Value> {
+    let body: Value = reqwest::Client::new()
+        .get(format!("{url}/api/collections/orders/records"))
+        .query(&[("filter", filter)])
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["items"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn checkout_places_one_order_per_store() {
+    let app = checkout_app().await;
+    let url = app.serve().await;
+    let token = login(&url, "buyer").await;
+
+    let (status, body) = checkout(&url, &token, "cart_stores", json!({ "address": "address_buyer" })).await;
+    assert_eq!(status, 200, "{body}");
+    let group = body["id"].as_str().unwrap();
+    assert_eq!(body["amount"], "30.10");
+    assert_eq!(body["quantity"], 4);
+    let orders = body["orders"].as_array().unwrap();
+    let summary: Vec<(&Value, &Value, &Value)> = orders
+        .iter()
+        .map(|order| (&order["store"], &order["amount"], &order["checkout"]))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (&json!("store_csv"), &json!("15.60"), &json!(group)),
+            (&json!("store_seller"), &json!("14.50"), &json!(group)),
+        ]
+    );
+    let items: Value = serde_json::from_str(orders[1]["items"].as_str().unwrap()).unwrap();
+    assert_eq!(items[0]["product"], "prod_book");
+
+    let filter = format!("checkout = '{group}'");
+    assert_eq!(list_orders(&url, &token, &filter).await.len(), 2);
+    let seller = login(&url, "seller").await;
+    let seen = list_orders(&url, &seller, &filter).await;
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0]["store"], "store_seller");
+
+    let customers: Vec<(String, i64, String)> =
+        sqlx::query_as("SELECT store, total_orders, total_spent FROM customers ORDER BY store")
+            .fetch_all(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        customers,
+        [
+            ("store_csv".to_string(), 1, "15.60".to_string()),
+            ("store_seller".to_string(), 1, "14.50".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn orders_are_only_created_by_checkout() {
+    let app = checkout_app().await;
+    let url = app.serve().await;
+    let token = login(&url, "buyer").await;
+
+    let order = json!({
+        "user": "user_buyer",
+        "store": "store_csv",
+        "items": "[{\"product\": \"prod_lamp\", \"quantity\": 100}]",
+        "amount": "0.01",
+        "name": "Buyer",
+        "email": "buyer@example.com",
+        "address": "address_buyer",
+    });
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/orders/records"))
+        .bearer_auth(&token)
+        .json(&order)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    let requests = json!([{ "method": "POST", "url": "/api/collections/orders/records", "body": order }]);
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/batch"))
+        .bearer_auth(&token)
+        .json(&json!({ "requests": requests }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["requests"]["0"]["response"]["status"], 403);
+
+    let orders: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE user = 'user_buyer'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(orders, 1);
+}
+
+#[tokio::test]
+async fn customers_are_the_buyer_accounts() {
+    let app = checkout_app().await;
+    let url = app.serve().await;
+    let token = login(&url, "buyer").await;
+    let (victim, _) = sign_up(&url, "victim").await;
+    sqlx::query(
+        "INSERT INTO customers (id, name, email, store, user, total_orders, total_spent) \
+         VALUES ('customer_victim', 'Victim', 'victim@example.com', 'store_csv', ?, 1, '5.00')",
+    )
+    .bind(&victim)
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    // The delivery details of checkout name the victim
+    let form = json!({ "address": "address_buyer", "name": "Mallory", "email": "victim@example.com" });
+    let (status, body) = checkout(&url, &token, "cart_buyer", form).await;
+    assert_eq!(status, 200, "{body}");
+    sqlx::query("INSERT INTO cart_items (id, cart, product, quantity) VALUES ('again_lamp', 'cart_buyer', 'prod_lamp', 1)")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let form = json!({ "address": "address_buyer", "name": "Someone else" });
+    let (status, body) = checkout(&url, &token, "cart_buyer", form).await;
+    assert_eq!(status, 200, "{body}");
+
+    let customers: Vec<(String, String, String, i64, String)> = sqlx::query_as(
+        "SELECT user, name, email, total_orders, total_spent FROM customers WHERE store = 'store_csv' ORDER BY rowid",
+    )
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    let row = |user: &str, name: &str, email: &str, orders: i64, spent: &str| {
+        (user.to_string(), name.to_string(), email.to_string(), orders, spent.to_string())
+    };
+    assert_eq!(
+        customers,
+        [
+            row(&victim, "Victim", "victim@example.com", 1, "5.00"),
+            row("user_buyer", "Mallory", "buyer@example.com", 2, "40.60"),
+        ]
+    );
+}
@@ -0,0 +1,100 @@
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+
+use super::{login, superuser, TestApp};
+use crate::errors::Error;
+
+async fn send(request: reqwest::RequestBuilder) -> (u16, Value) {
+    let response = request.send().await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+/// Runs a statement expected to fail and returns the error it maps to.
+async fn failing_query(app: &TestApp, sql: &str) -> Error {
+    sqlx::query(sql).execute(&app.pool).await.unwrap_err().into()
+}
+
+#[tokio::test]
+async fn constraint_violations_become_field_errors() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let token = login(&url, "csv").await;
+
+    let (status, body) = send(
+        reqwest::Client::new()
+            .post(format!("{url}/api/collections/stores/records"))
+            .bearer_auth(&token)
+            .json(&json!({ "name": "Another store", "slug": "csv-store", "user": "user_csv" })),
+    )
+    .await;
+    assert_eq!(status, 400, "{body}");
+    assert_eq!(body["data"]["slug"]["code"], "validation_not_unique");
+
+    let err = failing_query(&app, "INSERT INTO stores (id, name, slug) VALUES ('store_null', 'Null', 'null-store')").await;
+    assert_eq!(err.status_code(), 400);
+    assert_eq!(err.data()["user"]["code"], "validation_required");
+
+    let err = failing_query(
+        &app,
+        "INSERT INTO cart_items (id, cart, product, quantity) VALUES ('item_zero', 'cart_none', 'prod_csv', 0)",
+    )
+    .await;
+    assert_eq!(err.status_code(), 400);
+    assert_eq!(err.data()["quantity"]["code"], "validation_invalid_value");
+}
+
+#[tokio::test]
+async fn missing_relations_are_bad_requests() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+
+    let err = failing_query(
+        &app,
+        "INSERT INTO products (id, name, category, price, store) VALUES ('prod_fk', 'Lamp', 'cat_missing', '20', 'store_csv')",
+    )
+    .await;
+    assert_eq!(err.status_code(), 400);
+    assert_eq!(err.to_string(), "Failed to find all relation records with the provided ids.");
+}
+
+#[tokio::test]
+async fn deleting_referenced_records_conflicts() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let (_, token) = superuser(&app, &url, "admin@example.com").await;
+
+    // Products restrict the deletion of their category
+    let (status, body) = send(
+        reqwest::Client::new()
+            .delete(format!("{url}/api/collections/categories/records/cat_csv"))
+            .bearer_auth(&token),
+    )
+    .await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(body["message"], "The record is still referenced by products.");
+
+    let categories: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM categories WHERE id = 'cat_csv'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(categories, 1);
+}
+
+#[tokio::test]
+async fn internal_errors_hide_their_details() {
+    let app = TestApp::builder().build().await;
+    let err = failing_query(&app, "SELECT * FROM missing_table").await;
+    assert_eq!(err.status_code(), 500);
+
+    let response = err.into_response();
+    assert_eq!(response.status(), 500);
+    let correlation_id = response.headers()["x-correlation-id"].to_str().unwrap().to_string();
+    assert!(uuid::Uuid::parse_str(&correlation_id).is_ok());
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body,
+        json!({ "status": 500, "message": "Something went wrong while processing your request.", "data": {} })
+    );
+    assert!(!body.to_string().contains("missing_table"));
+}
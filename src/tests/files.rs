@@ -0,0 +1,267 @@
+use image::{DynamicImage, ImageFormat};
+use reqwest::multipart::{Form, Part};
+use serde_json::Value;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use super::{login, sign_up, TestApp};
+use crate::models::file::{max_upload_size, STORAGE};
+use crate::models::pocketbase::{Order, Product};
+use crate::models::record::Record;
+use crate::settings::SETTINGS;
+
+/// The PNG signature, enough for the content type to be detected.
+fn png() -> Part {
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    data.extend_from_slice(&[0; 64]);
+    Part::bytes(data).file_name("Blue Shirt.png").mime_str("image/png").unwrap()
+}
+
+/// A 1000x500 image in `format`.
+fn image(format: ImageFormat) -> Part {
+    let mut data = Cursor::new(Vec::new());
+    DynamicImage::new_rgb8(1000, 500).write_to(&mut data, format).unwrap();
+    let name = format!("photo.{}", format.extensions_str()[0]);
+    Part::bytes(data.into_inner()).file_name(name)
+}
+
+async fn patch(url: &str, token: &str, path: &str, form: Form) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .patch(format!("{url}/api/collections/{path}"))
+        .bearer_auth(token)
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+/// Creates a product of the catalog fixture's store with the images sent in
+/// `form`. Tests use their own product, as they share the storage directory.
+async fn create_product(url: &str, token: &str, form: Form) -> (u16, Value) {
+    let form = form
+        .text("name", "Photo")
+        .text("category", "cat_csv")
+        .text("store", "store_csv")
+        .text("price", "10");
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/products/records"))
+        .bearer_auth(token)
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+/// Path of a file in the local storage the tests run with.
+fn path(collection: &str, id: &str, name: &str) -> PathBuf {
+    Path::new(&SETTINGS.storage.dir).join(collection).join(id).join(name)
+}
+
+fn stored(collection: &str, id: &str, name: &str) -> bool {
+    path(collection, id, name).exists()
+}
+
+fn images(body: &Value) -> Vec<String> {
+    serde_json::from_str(body["images"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn uploads_replace_and_delete_files() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let (id, token) = sign_up(&url, "avatar").await;
+
+    let form = Form::new().text("name", "Avatar").part("avatar", png());
+    let (status, body) = patch(&url, &token, &format!("users/records/{id}"), form).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["name"], "Avatar");
+    let first = body["avatar"].as_str().unwrap().to_string();
+    assert!(first.starts_with("blue_shirt_") && first.ends_with(".png"), "{first}");
+    assert!(stored("users", &id, &first));
+
+    let text = Part::bytes(b"not an image".to_vec()).file_name("avatar.png").mime_str("image/png").unwrap();
+    let (status, body) = patch(&url, &token, &format!("users/records/{id}"), Form::new().part("avatar", text)).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["avatar"]["code"], "validation_invalid_mime_type");
+
+    let (status, body) = patch(&url, &token, &format!("users/records/{id}"), Form::new().part("avatar", png())).await;
+    assert_eq!(status, 200, "{body}");
+    let second = body["avatar"].as_str().unwrap().to_string();
+    assert_ne!(second, first);
+    assert!(!stored("users", &id, &first));
+    assert!(stored("users", &id, &second));
+
+    let response = reqwest::Client::new()
+        .delete(format!("{url}/api/collections/users/records/{id}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    assert!(!stored("users", &id, &second));
+}
+
+#[tokio::test]
+async fn images_can_be_appended_and_removed() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let token = &login(&url, "csv").await;
+
+    let form = Form::new().part("images", png()).part("images", png());
+    let (status, body) = create_product(&url, token, form).await;
+    assert_eq!(status, 200, "{body}");
+    let id = body["id"].as_str().unwrap().to_string();
+    let product = format!("products/records/{id}");
+    let added = images(&body);
+    assert_eq!(added.len(), 2);
+
+    let form = Form::new().text("images-", added[0].clone()).text("inventory", "15").text("active", "true");
+    let (status, body) = patch(&url, token, &product, form).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(images(&body), vec![added[1].clone()]);
+    assert_eq!(body["inventory"], 15);
+    assert_eq!(body["active"], true);
+    assert!(!stored("products", &id, &added[0]));
+    assert!(stored("products", &id, &added[1]));
+
+    let form = (0..10).fold(Form::new(), |form, _| form.part("images+", png()));
+    let (status, body) = patch(&url, token, &product, form).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["images"]["code"], "validation_max_select_limit");
+
+    STORAGE.remove_record("products", &id).await;
+}
+
+async fn dimensions(url: &str, path: &str) -> (String, (u32, u32)) {
+    let response = reqwest::get(format!("{url}/api/files/{path}")).await.unwrap();
+    assert_eq!(response.status(), 200, "{path}");
+    let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+    let image = image::load_from_memory(&response.bytes().await.unwrap()).unwrap();
+    (content_type, (image.width(), image.height()))
+}
+
+#[tokio::test]
+async fn files_are_served_with_thumbnails() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let token = login(&url, "csv").await;
+
+    let form = Form::new()
+        .part("images", image(ImageFormat::Png))
+        .part("images", image(ImageFormat::WebP));
+    let (status, body) = create_product(&url, &token, form).await;
+    assert_eq!(status, 200, "{body}");
+    let id = body["id"].as_str().unwrap().to_string();
+    let [png, webp] = <[String; 2]>::try_from(images(&body)).unwrap();
+
+    let response = reqwest::get(format!("{url}/api/files/products/{id}/{png}")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["cache-control"].to_str().unwrap().contains("max-age"));
+    let response = reqwest::Client::new()
+        .get(format!("{url}/api/files/products/{id}/{png}"))
+        .header("range", "bytes=0-7")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(&response.bytes().await.unwrap()[..], b"\x89PNG\r\n\x1a\n");
+
+    let product = format!("products/{id}/{png}");
+    assert_eq!(dimensions(&url, &product).await, ("image/png".to_string(), (1000, 500)));
+    for (thumb, size) in [
+        ("100x100", (100, 100)),
+        ("400x400t", (400, 400)),
+        ("400x400f", (400, 200)),
+        ("0x400", (800, 400)),
+        // Not one of the sizes of the field
+        ("123x45", (1000, 500)),
+    ] {
+        let (_, dimensions) = dimensions(&url, &format!("{product}?thumb={thumb}")).await;
+        assert_eq!(dimensions, size, "{thumb}");
+    }
+    let thumbs = path("products", &id, &format!("thumbs_{png}"));
+    assert!(thumbs.join(format!("100x100_{png}")).exists());
+    assert_eq!(
+        dimensions(&url, &format!("products/{id}/{webp}?thumb=100x100")).await,
+        ("image/webp".to_string(), (100, 100))
+    );
+
+    let response = reqwest::get(format!("{url}/api/files/products/{id}/missing.png")).await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    let (status, body) = patch(&url, &token, &format!("products/records/{id}"), Form::new().text("images-", png.clone())).await;
+    assert_eq!(status, 200, "{body}");
+    let response = reqwest::get(format!("{url}/api/files/{product}")).await.unwrap();
+    assert_eq!(response.status(), 404);
+    assert!(!thumbs.exists());
+
+    STORAGE.remove_record("products", &id).await;
+}
+
+async fn file_token(url: &str, token: &str) -> String {
+    let body: Value = reqwest::Client::new()
+        .post(format!("{url}/api/files/token"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn protected_files_need_a_file_token() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let owner = login(&url, "csv").await;
+    let buyer = login(&url, "buyer").await;
+    let (_, stranger) = sign_up(&url, "stranger").await;
+
+    let invoice = Part::bytes(b"%PDF-1.4\n%%EOF\n".to_vec()).file_name("Invoice 42.pdf");
+    let (status, body) = patch(&url, &owner, "orders/records/order_buyer", Form::new().part("invoice", invoice)).await;
+    assert_eq!(status, 200, "{body}");
+    let invoice = body["invoice"].as_str().unwrap().to_string();
+    let file = format!("{url}/api/files/orders/order_buyer/{invoice}");
+
+    let response = reqwest::get(&file).await.unwrap();
+    assert_eq!(response.status(), 403);
+    // Access tokens are not file tokens
+    let response = reqwest::get(format!("{file}?token={buyer}")).await.unwrap();
+    assert_eq!(response.status(), 403);
+    let response = reqwest::get(format!("{file}?token={}", file_token(&url, &stranger).await)).await.unwrap();
+    assert_eq!(response.status(), 403);
+
+    let token = file_token(&url, &buyer).await;
+    let response = reqwest::get(format!("{file}?token={token}")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/pdf");
+    assert_eq!(response.headers()["cache-control"], "private, no-store");
+    let response = reqwest::get(format!("{file}?token={}", file_token(&url, &owner).await)).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    // File tokens are not access tokens
+    let response = reqwest::Client::new()
+        .get(format!("{url}/api/collections/orders/records/order_buyer"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    STORAGE.remove_record("orders", "order_buyer").await;
+}
+
+#[test]
+fn upload_limits_cover_every_file_of_a_record() {
+    // An order holds an invoice and up to ten downloads of 20 MiB
+    assert_eq!(max_upload_size(&[Order::FILE_FIELDS]), 11 * 20 * 1024 * 1024);
+    assert_eq!(max_upload_size(&[Product::FILE_FIELDS, Order::FILE_FIELDS]), 11 * 20 * 1024 * 1024);
+}
@@ -0,0 +1,2 @@
+INSERT INTO categories (id, name, slug, description)
+VALUES ('cat_fixture', 'Fixture', 'fixture', 'Loaded by a test fixture.');
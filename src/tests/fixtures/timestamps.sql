@@ -0,0 +1,6 @@
+-- Categories written with SQLite's CURRENT_TIMESTAMP format and the one of
+-- the application
+INSERT INTO categories (id, name, slug, created, updated) VALUES
+    ('legacy000000001', 'Legacy', 'legacy', '2024-09-01 10:00:00', '2024-09-01 10:00:00'),
+    ('current00000001', 'Current', 'current', '2024-09-01 10:00:00.500Z', '2024-09-01 10:00:00.500Z'),
+    ('later0000000001', 'Later', 'later', '2024-09-02 08:30:00.000Z', '2024-09-02 08:30:00.000Z');
@@ -0,0 +1,109 @@
+use serde_json::{json, Value};
+
+use super::{login, superuser, TestApp};
+use crate::models::auth::AuthModel;
+
+async fn impersonate(url: &str, token: &str, user: &str, form: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/impersonate/{user}"))
+        .bearer_auth(token)
+        .json(&form)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn audit_logs(app: &TestApp) -> Vec<(String, String, Option<String>, Value)> {
+    let rows: Vec<(String, String, Option<String>, String)> =
+        sqlx::query_as("SELECT actor, action, target, details FROM audit_logs ORDER BY created, rowid")
+            .fetch_all(&app.pool)
+            .await
+            .unwrap();
+    rows.into_iter()
+        .map(|(actor, action, target, details)| (actor, action, target, serde_json::from_str(&details).unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn superusers_impersonate_users_with_audited_tokens() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let (admin, token) = superuser(&app, &url, "admin@example.com").await;
+
+    let (status, body) = impersonate(&url, &token, "user_buyer", json!({ "duration": 60 })).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["record"]["id"], "user_buyer");
+    assert!(body.get("refreshToken").is_none());
+    let impersonation = body["token"].as_str().unwrap();
+    let claims = AuthModel::new().verify_token(impersonation).unwrap();
+    assert_eq!(claims.user_id, "user_buyer");
+    assert_eq!(claims.impersonator.as_deref(), Some(admin.as_str()));
+    assert_eq!(claims.refresh_uuid, None);
+    assert!(!claims.is_superuser());
+    let lifetime = claims.exp - chrono::Utc::now().timestamp();
+    assert!((50..=60).contains(&lifetime), "{lifetime}");
+
+    // Reads are only logged, changes are audited
+    let client = reqwest::Client::new();
+    let record = format!("{url}/api/collections/users/records/user_buyer");
+    let status = client.get(&record).bearer_auth(impersonation).send().await.unwrap().status();
+    assert_eq!(status, 200);
+    let status = client
+        .patch(&record)
+        .bearer_auth(impersonation)
+        .json(&json!({ "name": "Impersonated" }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, 200);
+    assert_eq!(
+        audit_logs(&app).await,
+        [
+            (admin.clone(), "impersonate".to_string(), Some("user_buyer".to_string()), json!({ "duration": 60 })),
+            (
+                admin.clone(),
+                "impersonated_request".to_string(),
+                Some("user_buyer".to_string()),
+                json!({ "method": "PATCH", "uri": "/api/collections/users/records/user_buyer", "status": 200 }),
+            ),
+        ]
+    );
+
+    // Impersonation tokens cannot impersonate in turn or mint API keys
+    let (status, _) = impersonate(&url, impersonation, "user_buyer", json!({})).await;
+    assert_eq!(status, 403);
+    let status = client
+        .post(format!("{url}/api/stores/store_csv/api-keys"))
+        .bearer_auth(impersonation)
+        .json(&json!({ "name": "Sync", "scopes": ["orders:read"] }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, 403);
+}
+
+#[tokio::test]
+async fn only_users_can_be_impersonated() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let (_, token) = superuser(&app, &url, "admin@example.com").await;
+    let (other, _) = superuser(&app, &url, "other@example.com").await;
+
+    let (status, _) = impersonate(&url, &token, &other, json!({})).await;
+    assert_eq!(status, 404);
+    let user = login(&url, "buyer").await;
+    let (status, _) = impersonate(&url, &user, "user_buyer", json!({})).await;
+    assert_eq!(status, 403);
+    assert!(audit_logs(&app).await.is_empty());
+}
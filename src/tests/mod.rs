@@ -0,0 +1,223 @@
+use axum::Router;
+use sqlx::{Executor, SqlitePool};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use crate::app;
+use crate::db;
+use crate::models::fixture::{self, Dataset, OnConflict};
+use crate::models::pocketbase::{current_timestamp, generate_id};
+use crate::models::user::hash_password;
+use crate::settings::{Database, SETTINGS};
+
+mod api_keys;
+mod batch;
+mod builder;
+mod carts;
+mod checkout;
+mod errors;
+mod files;
+mod impersonation;
+mod orders;
+mod passwords;
+mod realtime;
+mod records;
+mod seed;
+mod storage;
+mod users;
+
+/// Where a test app keeps its database.
+enum Storage {
+    Memory,
+    TempFile,
+}
+
+/// Builds the full application router against a fresh database with the
+/// migrations applied, so tests can run in parallel without sharing state.
+pub struct TestAppBuilder {
+    storage: Storage,
+    fixtures: Vec<PathBuf>,
+}
+
+impl TestAppBuilder {
+    /// Uses a temporary database file instead of `sqlite::memory:`, for tests
+    /// that need several independent connections or to inspect the file.
+    pub fn temp_file(mut self) -> Self {
+        self.storage = Storage::TempFile;
+        self
+    }
+
+    /// Data loaded after the migrations: an `.sql` script, or a JSON/CSV
+    /// dataset imported with the fixture loader, which must not contain
+    /// invalid records.
+    pub fn fixture(mut self, path: impl AsRef<Path>) -> Self {
+        self.fixtures.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub async fn build(self) -> TestApp {
+        let temp_file = match self.storage {
+            Storage::Memory => None,
+            Storage::TempFile => {
+                Some(std::env::temp_dir().join(format!("vieshare-axum-test-{}.db", Uuid::new_v4())))
+            }
+        };
+        let uri = match &temp_file {
+            Some(path) => format!("sqlite://{}", path.display()),
+            None => "sqlite::memory:".to_string(),
+        };
+        let settings = Database {
+            uri,
+            replica: None,
+            ..SETTINGS.database.clone()
+        };
+
+        let pools = db::connect(&settings).await.expect("Failed to create test database");
+        db::migrate(&pools.primary, false).await.expect("Failed to migrate test database");
+        let mut dataset = Dataset::default();
+        for fixture in &self.fixtures {
+            if fixture.extension().is_some_and(|extension| extension == "sql") {
+                let sql = std::fs::read_to_string(fixture)
+                    .unwrap_or_else(|err| panic!("Failed to read fixture {}: {}", fixture.display(), err));
+                pools
+                    .primary
+                    .execute(sql.as_str())
+                    .await
+                    .unwrap_or_else(|err| panic!("Failed to load fixture {}: {}", fixture.display(), err));
+            } else {
+                dataset
+                    .add(&pools.primary, fixture)
+                    .await
+                    .unwrap_or_else(|err| panic!("Failed to read fixture {}: {}", fixture.display(), err));
+            }
+        }
+        let report = fixture::import(&pools.primary, &dataset, OnConflict::Update)
+            .await
+            .expect("Failed to import fixtures");
+        if let Some(error) = report.errors.first() {
+            panic!("Invalid fixture record: {error}");
+        }
+
+        let pool = pools.primary.clone();
+        let router = app::create_app(pools.primary, pools.read).await;
+
+        TestApp {
+            router,
+            pool,
+            temp_file,
+        }
+    }
+}
+
+pub struct TestApp {
+    pub router: Router,
+    pub pool: SqlitePool,
+    temp_file: Option<PathBuf>,
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        if let Some(path) = &self.temp_file {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+            }
+        }
+    }
+}
+
+impl TestApp {
+    pub fn builder() -> TestAppBuilder {
+        TestAppBuilder {
+            storage: Storage::Memory,
+            fixtures: Vec::new(),
+        }
+    }
+
+    /// Serves the app on a random local port and returns its base url.
+    pub async fn serve(&self) -> String {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .expect("Failed to bind test server");
+        let address = listener.local_addr().expect("Failed to read test server address");
+        let router = self.router.clone();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        format!("http://{address}")
+    }
+}
+
+/// Registers and logs in a user, returning its id and access token.
+pub async fn sign_up(url: &str, username: &str) -> (String, String) {
+    let client = reqwest::Client::new();
+    let password = "correct horse battery staple";
+    let response = client
+        .post(format!("{url}/api/collections/users/records"))
+        .json(&serde_json::json!({
+            "email": format!("{username}@example.com"),
+            "username": username,
+            "password": password,
+            "passwordConfirm": password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200, "failed to register {username}");
+
+    let body: serde_json::Value = client
+        .post(format!("{url}/api/collections/users/auth-with-password"))
+        .json(&serde_json::json!({ "identity": username, "password": password }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    (
+        body["record"]["id"].as_str().unwrap().to_string(),
+        body["token"].as_str().unwrap().to_string(),
+    )
+}
+
+/// Logs in a fixture user, whose password is always the same, and returns
+/// its access token.
+pub async fn login(url: &str, identity: &str) -> String {
+    let body: serde_json::Value = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/auth-with-password"))
+        .json(&serde_json::json!({ "identity": identity, "password": "correct horse battery staple" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["token"].as_str().unwrap().to_string()
+}
+
+/// Creates a superuser and returns its id and access token.
+pub async fn superuser(app: &TestApp, url: &str, email: &str) -> (String, String) {
+    let id = generate_id();
+    let now = current_timestamp();
+    sqlx::query("INSERT INTO _superusers (id, email, password_hash, created, updated) VALUES (?, ?, ?, ?, ?)")
+        .bind(&id)
+        .bind(email)
+        .bind(hash_password("correct horse battery staple".to_string()).await.unwrap())
+        .bind(&now)
+        .bind(&now)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let body: serde_json::Value = reqwest::Client::new()
+        .post(format!("{url}/api/collections/_superusers/auth-with-password"))
+        .json(&serde_json::json!({ "identity": email, "password": "correct horse battery staple" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    (id, body["token"].as_str().unwrap().to_string())
+}
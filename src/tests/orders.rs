@@ -0,0 +1,192 @@
+use serde_json::{json, Value};
+
+use super::{login, sign_up, TestApp};
+
+async fn place_orders(url: &str, token: &str) -> Vec<Value> {
+    let body: Value = reqwest::Client::new()
+        .post(format!("{url}/api/carts/cart_stores/checkout"))
+        .bearer_auth(token)
+        .json(&json!({ "address": "address_buyer" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["orders"].as_array().unwrap().clone()
+}
+
+async fn change_status(url: &str, token: &str, order: &str, form: Value) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/orders/{order}/status"))
+        .bearer_auth(token)
+        .json(&form)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+async fn history(url: &str, token: &str, order: &str) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(format!("{url}/api/orders/{order}/history"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn order_status_follows_the_lifecycle() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let buyer = login(&url, "buyer").await;
+    let seller = login(&url, "seller").await;
+    let orders = place_orders(&url, &buyer).await;
+    let order = orders[1]["id"].as_str().unwrap();
+    assert_eq!(orders[1]["store"], "store_seller");
+
+    // Only the store confirms payments
+    let (status, _) = change_status(&url, &buyer, order, json!({ "status": "paid" })).await;
+    assert_eq!(status, 403);
+    for next in ["paid", "processing", "shipped"] {
+        let (status, body) = change_status(&url, &seller, order, json!({ "status": next })).await;
+        assert_eq!(status, 200, "{body}");
+        assert_eq!(body["status"], next);
+    }
+    let (status, body) = change_status(&url, &seller, order, json!({ "status": "paid" })).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["message"], "The order cannot go from shipped to paid.");
+    let (status, _) = change_status(&url, &buyer, order, json!({ "status": "cancelled" })).await;
+    assert_eq!(status, 409);
+    let (status, _) = change_status(&url, &buyer, order, json!({ "status": "lost" })).await;
+    assert_eq!(status, 400);
+    let (status, body) =
+        change_status(&url, &buyer, order, json!({ "status": "delivered", "note": "Left at the door" })).await;
+    assert_eq!(status, 200, "{body}");
+
+    // The status cannot be changed through the record API
+    let body: Value = reqwest::Client::new()
+        .patch(format!("{url}/api/collections/orders/records/{order}"))
+        .bearer_auth(&seller)
+        .json(&json!({ "status": "pending", "notes": "Gift" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!((&body["status"], &body["notes"]), (&json!("delivered"), &json!("Gift")));
+
+    let (status, body) = history(&url, &buyer, order).await;
+    assert_eq!(status, 200, "{body}");
+    let changes: Vec<(&Value, &Value)> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| (&change["from_status"], &change["to_status"]))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (&json!("pending"), &json!("paid")),
+            (&json!("paid"), &json!("processing")),
+            (&json!("processing"), &json!("shipped")),
+            (&json!("shipped"), &json!("delivered")),
+        ]
+    );
+    assert_eq!(body["items"][0]["actor"], "user_seller");
+    assert_eq!(body["items"][3]["actor"], "user_buyer");
+    assert_eq!(body["items"][3]["note"], "Left at the door");
+
+    // Orders of others are not found
+    let (_, stranger) = sign_up(&url, "stranger").await;
+    let (status, _) = history(&url, &stranger, order).await;
+    assert_eq!(status, 404);
+    let (status, _) = change_status(&url, &stranger, order, json!({ "status": "refunded" })).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn cancelled_orders_are_restocked() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let buyer = login(&url, "buyer").await;
+    let orders = place_orders(&url, &buyer).await;
+    let order = orders[0]["id"].as_str().unwrap();
+
+    let (status, body) = change_status(&url, &buyer, order, json!({ "status": "cancelled" })).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["status"], "cancelled");
+    let inventory: Vec<(String, i64)> =
+        sqlx::query_as("SELECT id, inventory FROM products WHERE id IN ('prod_lamp', 'prod_mug', 'prod_book') ORDER BY id")
+            .fetch_all(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        inventory,
+        [
+            ("prod_book".to_string(), 2),
+            ("prod_lamp".to_string(), 5),
+            ("prod_mug".to_string(), 1),
+        ]
+    );
+
+    let (status, _) = change_status(&url, &buyer, order, json!({ "status": "cancelled" })).await;
+    assert_eq!(status, 409);
+    let (_, body) = history(&url, &buyer, order).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn cancelling_only_restocks_what_checkout_ordered() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .fixture("src/tests/fixtures/orders.json")
+        .fixture("src/tests/fixtures/checkout.json")
+        .build()
+        .await;
+    let url = app.serve().await;
+    let buyer = login(&url, "buyer").await;
+    let owner = login(&url, "csv").await;
+    let orders = place_orders(&url, &buyer).await;
+    let order = orders[0]["id"].as_str().unwrap();
+    let items = orders[0]["items"].clone();
+
+    let response = reqwest::Client::new()
+        .patch(format!("{url}/api/collections/orders/records/{order}"))
+        .bearer_auth(&owner)
+        .json(&json!({ "items": "[{\"product\": \"prod_csv\", \"quantity\": 100000}]", "amount": "0", "notes": "Gift" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["items"], items);
+    assert_eq!(body["amount"], orders[0]["amount"]);
+    assert_eq!(body["notes"], "Gift");
+
+    // The fixture order was not placed by checkout, so nothing was taken out
+    // of stock for it
+    for order in [order, "order_buyer"] {
+        let (status, body) = change_status(&url, &buyer, order, json!({ "status": "cancelled" })).await;
+        assert_eq!(status, 200, "{body}");
+    }
+    let inventory: Vec<(String, i64)> =
+        sqlx::query_as("SELECT id, inventory FROM products WHERE id IN ('prod_csv', 'prod_lamp') ORDER BY id")
+            .fetch_all(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(inventory, [("prod_csv".to_string(), 12), ("prod_lamp".to_string(), 5)]);
+}
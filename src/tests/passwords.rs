@@ -0,0 +1,136 @@
+use serde_json::{json, Value};
+
+use super::{sign_up, TestApp};
+use crate::models::password::{PasswordPolicy, Passwords, Verification, PASSWORDS};
+use crate::settings::{self, PasswordAlgorithm, SETTINGS};
+
+async fn password_hash(app: &TestApp, username: &str) -> String {
+    sqlx::query_scalar("SELECT password_hash FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+}
+
+async fn auth_with_password(url: &str, identity: &str, password: &str) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/auth-with-password"))
+        .json(&json!({ "identity": identity, "password": password }))
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn passwords_are_hashed_with_argon2id() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    sign_up(&url, "hashed").await;
+
+    let hash = password_hash(&app, "hashed").await;
+    assert!(hash.starts_with("$argon2id$v=19$"), "{hash}");
+    assert!(!hash.contains("correct horse"));
+}
+
+#[tokio::test]
+async fn bcrypt_hashes_are_upgraded_on_login() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    sign_up(&url, "legacy").await;
+    let legacy = bcrypt::hash("correct horse battery staple", 4).unwrap();
+    sqlx::query("UPDATE users SET password_hash = ? WHERE username = 'legacy'")
+        .bind(&legacy)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let (status, _) = auth_with_password(&url, "legacy", "wrong horse battery staple").await;
+    assert_eq!(status, 401);
+    assert_eq!(password_hash(&app, "legacy").await, legacy);
+
+    let (status, body) = auth_with_password(&url, "legacy", "correct horse battery staple").await;
+    assert_eq!(status, 200, "{body}");
+    let hash = password_hash(&app, "legacy").await;
+    assert!(hash.starts_with("$argon2id$"), "{hash}");
+    let (status, _) = auth_with_password(&url, "legacy", "correct horse battery staple").await;
+    assert_eq!(status, 200);
+    assert_eq!(password_hash(&app, "legacy").await, hash);
+}
+
+#[tokio::test]
+async fn unknown_accounts_fail_like_wrong_passwords() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    sign_up(&url, "known").await;
+
+    let wrong_password = auth_with_password(&url, "known", "wrong horse battery staple").await;
+    let unknown_account = auth_with_password(&url, "unknown", "wrong horse battery staple").await;
+    assert_eq!(wrong_password.0, 401);
+    assert_eq!(wrong_password, unknown_account);
+}
+
+#[tokio::test]
+async fn weak_passwords_are_rejected_on_sign_up() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/records"))
+        .json(&json!({
+            "email": "weak@example.com",
+            "username": "weak",
+            "password": "short",
+            "passwordConfirm": "short",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["password"]["code"], "validation_min_text_constraint");
+}
+
+#[test]
+fn breached_passwords_are_rejected() {
+    let path = std::env::temp_dir().join(format!("vieshare-axum-breached-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "# Top passwords\nPassword123\n\nletmein123\n").unwrap();
+    let settings = settings::Password {
+        breached_list: Some(path.display().to_string()),
+        ..SETTINGS.auth.password.clone()
+    };
+    let policy = PasswordPolicy::from_settings(&settings).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let codes: Vec<Option<&str>> = ["password123", "LetMeIn123", "correct horse battery staple", "short", &"x".repeat(129)]
+        .iter()
+        .map(|password| policy.check(password).err().map(|err| err.code()))
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            Some("validation_breached_password"),
+            Some("validation_breached_password"),
+            None,
+            Some("validation_min_text_constraint"),
+            Some("validation_max_text_constraint"),
+        ]
+    );
+}
+
+#[test]
+fn hashes_of_other_algorithms_need_a_rehash() {
+    let settings = settings::Password {
+        algorithm: PasswordAlgorithm::Bcrypt,
+        ..SETTINGS.auth.password.clone()
+    };
+    let bcrypt = Passwords::from_settings(&settings).unwrap();
+    let hash = bcrypt.hash("correct horse battery staple").unwrap();
+    assert!(hash.starts_with("$2"), "{hash}");
+
+    assert_eq!(PASSWORDS.verify("correct horse battery staple", &hash).unwrap(), Verification::MatchNeedsRehash);
+    assert_eq!(PASSWORDS.verify("wrong horse battery staple", &hash).unwrap(), Verification::Mismatch);
+    let current = PASSWORDS.hash("correct horse battery staple").unwrap();
+    assert_eq!(PASSWORDS.verify("correct horse battery staple", &current).unwrap(), Verification::Match);
+    assert!(PASSWORDS.verify("correct horse battery staple", "plain text").is_err());
+}
@@ -0,0 +1,342 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use super::{login, sign_up, TestApp};
+use crate::models::auth::AuthModel;
+use crate::models::realtime::{EventAction, Realtime, Subscription};
+use crate::utils::auth::{Auth, AuthUser};
+
+/// Reads the events of an `/api/realtime` connection.
+struct Events {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl Events {
+    async fn connect(url: &str) -> (Self, String) {
+        let response = reqwest::get(format!("{url}/api/realtime")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let mut events = Self {
+            response,
+            buffer: String::new(),
+        };
+
+        let (name, data) = events.next().await;
+        assert_eq!(name, "PB_CONNECT");
+        (events, data["clientId"].as_str().unwrap().to_string())
+    }
+
+    async fn next(&mut self) -> (String, Value) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some((event, rest)) = self.buffer.split_once("\n\n") {
+                    let event = event.to_string();
+                    self.buffer = rest.to_string();
+                    let field = |name: &str| {
+                        event
+                            .lines()
+                            .find_map(|line| line.strip_prefix(&format!("{name}:")))
+                            .map(|value| value.trim_start().to_string())
+                    };
+                    // Keep-alive comments have no event
+                    if let (Some(name), Some(data)) = (field("event"), field("data")) {
+                        return (name, serde_json::from_str(&data).unwrap());
+                    }
+                    continue;
+                }
+                let chunk = self.response.chunk().await.unwrap().expect("event stream closed");
+                self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+            }
+        })
+        .await
+        .expect("timed out waiting for an event")
+    }
+
+    /// Waits for the server to end the stream, skipping keep-alives.
+    async fn closed(mut self) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(chunk) = self.response.chunk().await.unwrap() {
+                assert!(!String::from_utf8_lossy(&chunk).contains("event:"), "unexpected event");
+            }
+        })
+        .await
+        .expect("timed out waiting for the stream to end")
+    }
+}
+
+async fn subscribe(url: &str, client_id: &str, token: Option<&str>, subscriptions: Value) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(format!("{url}/api/realtime"))
+        .json(&json!({ "clientId": client_id, "subscriptions": subscriptions }));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap()
+}
+
+#[tokio::test]
+async fn events_follow_view_rules_and_filters() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let client = reqwest::Client::new();
+    let (user_id, token) = sign_up(&url, "owner").await;
+
+    let (mut anonymous, anonymous_id) = Events::connect(&url).await;
+    let response = subscribe(&url, &anonymous_id, None, json!(["users/*", "stores/*"])).await;
+    assert_eq!(response.status(), 204);
+
+    let (mut owner, owner_id) = Events::connect(&url).await;
+    let filtered = format!(
+        "stores/*?options={}",
+        form_urlencoded::byte_serialize(json!({ "query": { "filter": "name ~ 'Second'", "expand": "user" } }).to_string().as_bytes())
+            .collect::<String>()
+    );
+    let response = subscribe(&url, &owner_id, Some(&token), json!([format!("users/{user_id}"), filtered])).await;
+    assert_eq!(response.status(), 204);
+
+    // Only visible to the user itself
+    let response = client
+        .patch(format!("{url}/api/collections/users/records/{user_id}"))
+        .bearer_auth(&token)
+        .json(&json!({ "name": "Owner" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    for name in ["First store", "Second store"] {
+        let response = client
+            .post(format!("{url}/api/collections/stores/records"))
+            .bearer_auth(&token)
+            .json(&json!({ "name": name, "slug": name.to_lowercase().replace(' ', "-"), "user": user_id }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let (name, data) = owner.next().await;
+    assert_eq!(name, format!("users/{user_id}"));
+    assert_eq!((data["action"].as_str(), data["record"]["name"].as_str()), (Some("update"), Some("Owner")));
+    let (name, data) = owner.next().await;
+    assert_eq!(name, filtered);
+    assert_eq!(data["record"]["name"], "Second store");
+    assert_eq!(data["record"]["expand"]["user"]["id"], user_id);
+
+    for expected in ["First store", "Second store"] {
+        let (name, data) = anonymous.next().await;
+        assert_eq!(name, "stores/*");
+        assert_eq!((data["action"].as_str(), data["record"]["name"].as_str()), (Some("create"), Some(expected)));
+    }
+}
+
+#[tokio::test]
+async fn subscriptions_are_validated() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let (_, token) = sign_up(&url, "subscriber").await;
+    let (_, other_token) = sign_up(&url, "other").await;
+    let (_events, client_id) = Events::connect(&url).await;
+
+    let response = subscribe(&url, "unknown", None, json!(["stores/*"])).await;
+    assert_eq!(response.status(), 404);
+
+    for topic in ["stores", "unknown/*", "stores/*?options={\"query\":{\"filter\":\"missing = 1\"}}"] {
+        let response = subscribe(&url, &client_id, None, json!([topic])).await;
+        assert_eq!(response.status(), 400, "{topic}");
+    }
+
+    let response = subscribe(&url, &client_id, Some(&token), json!(["stores/*"])).await;
+    assert_eq!(response.status(), 204);
+    let response = subscribe(&url, &client_id, Some(&other_token), json!(["stores/*"])).await;
+    assert_eq!(response.status(), 403);
+}
+
+type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn send(socket: &mut Socket, message: Value) {
+    socket.send(WsMessage::Text(message.to_string())).await.unwrap();
+}
+
+/// Next JSON message, skipping the heartbeats.
+async fn receive(socket: &mut Socket) -> Value {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match socket.next().await.expect("socket closed").unwrap() {
+                WsMessage::Text(text) => return serde_json::from_str(&text).unwrap(),
+                WsMessage::Close(frame) => panic!("socket closed: {frame:?}"),
+                _ => continue,
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for a message")
+}
+
+#[tokio::test]
+async fn websocket_carries_the_same_protocol() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let ws_url = format!("{}/api/realtime/ws", url.replace("http://", "ws://"));
+    let (user_id, token) = sign_up(&url, "socket").await;
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(&ws_url).await.unwrap();
+    send(&mut socket, json!({ "type": "auth", "token": token })).await;
+    let connected = receive(&mut socket).await;
+    assert_eq!(connected["type"], "connect");
+    assert!(connected["clientId"].is_string());
+
+    let topic = format!("users/{user_id}");
+    send(&mut socket, json!({ "type": "subscribe", "subscriptions": [topic, "stores/*"] })).await;
+    assert_eq!(receive(&mut socket).await, json!({ "type": "subscriptions", "subscriptions": [topic, "stores/*"] }));
+    send(&mut socket, json!({ "type": "subscribe", "subscriptions": ["unknown/*"] })).await;
+    let error = receive(&mut socket).await;
+    assert_eq!((error["type"].as_str(), error["status"].as_u64()), (Some("error"), Some(400)));
+    send(&mut socket, json!({ "type": "unsubscribe", "subscriptions": ["stores/*"] })).await;
+    assert_eq!(receive(&mut socket).await["subscriptions"], json!([topic]));
+
+    let response = reqwest::Client::new()
+        .patch(format!("{url}/api/collections/users/records/{user_id}"))
+        .bearer_auth(&token)
+        .json(&json!({ "name": "Socket" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let event = receive(&mut socket).await;
+    assert_eq!((event["type"].as_str(), event["name"].as_str()), (Some("event"), Some(topic.as_str())));
+    assert_eq!(event["data"]["action"], "update");
+    assert_eq!(event["data"]["record"]["name"], "Socket");
+
+    // Invalid credentials close the connection
+    let (mut socket, _) = tokio_tungstenite::connect_async(&ws_url).await.unwrap();
+    send(&mut socket, json!({ "type": "auth", "token": "invalid" })).await;
+    assert_eq!(receive(&mut socket).await["status"], 401);
+    let closed = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap();
+    assert!(matches!(closed, Some(Ok(WsMessage::Close(Some(frame)))) if u16::from(frame.code) == 1008));
+}
+
+#[tokio::test]
+async fn websockets_close_when_the_token_expires() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let ws_url = format!("{}/api/realtime/ws", url.replace("http://", "ws://"));
+    let (user_id, _) = sign_up(&url, "expiring").await;
+    let token = AuthModel::new()
+        .create_impersonation_token(&user_id, "admin", chrono::Duration::seconds(2))
+        .unwrap();
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(&ws_url).await.unwrap();
+    send(&mut socket, json!({ "type": "auth", "token": token })).await;
+    assert_eq!(receive(&mut socket).await["type"], "connect");
+
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match socket.next().await {
+                Some(Ok(WsMessage::Close(frame))) => return frame,
+                Some(Ok(_)) => continue,
+                other => panic!("socket not closed properly: {other:?}"),
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the socket to close");
+    assert!(matches!(closed, Some(frame) if u16::from(frame.code) == 1008 && frame.reason == "Token expired"));
+}
+
+#[tokio::test]
+async fn slow_clients_are_disconnected() {
+    let app = TestApp::builder().build().await;
+    let realtime = Realtime::new(app.pool.clone());
+    let (client_id, mut receiver) = realtime.connect();
+    let subscription = Subscription::parse("categories/*").unwrap();
+    realtime
+        .subscribe(&client_id, None, vec![("categories/*".to_string(), subscription)])
+        .unwrap();
+
+    for i in 0..200 {
+        realtime.publish("categories", EventAction::Create, json!({ "id": format!("category{i}") }));
+    }
+    // Not reading while the events are delivered
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut received = 0;
+    while tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("timed out waiting for an event")
+        .is_some()
+    {
+        received += 1;
+    }
+    assert!(received > 0 && received < 200, "received {received} events");
+}
+
+#[tokio::test]
+async fn expired_credentials_stop_the_events() {
+    let app = TestApp::builder().build().await;
+    let realtime = Realtime::new(app.pool.clone());
+    let (client_id, mut receiver) = realtime.connect();
+    let auth = Auth::User(AuthUser {
+        id: "user_expired".to_string(),
+        impersonator: None,
+        expires_at: chrono::Utc::now().timestamp() - 1,
+    });
+    let subscription = Subscription::parse("categories/*").unwrap();
+    realtime
+        .subscribe(&client_id, Some(auth), vec![("categories/*".to_string(), subscription)])
+        .unwrap();
+
+    realtime.publish("categories", EventAction::Create, json!({ "id": "category1" }));
+    let received = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("timed out waiting for the disconnection");
+    assert!(received.is_none());
+}
+
+#[tokio::test]
+async fn revoked_api_keys_stop_the_events() {
+    let app = TestApp::builder().fixture("src/tests/fixtures/catalog").build().await;
+    let url = app.serve().await;
+    let client = reqwest::Client::new();
+    let owner = login(&url, "csv").await;
+
+    let key: Value = client
+        .post(format!("{url}/api/stores/store_csv/api-keys"))
+        .bearer_auth(&owner)
+        .json(&json!({ "name": "Sync", "scopes": ["products:read"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let api_key = key["key"].as_str().unwrap();
+
+    let (mut events, client_id) = Events::connect(&url).await;
+    let response = subscribe(&url, &client_id, Some(api_key), json!(["products/prod_csv"])).await;
+    assert_eq!(response.status(), 204);
+
+    let update = |name: &'static str| {
+        client
+            .patch(format!("{url}/api/collections/products/records/prod_csv"))
+            .bearer_auth(&owner)
+            .json(&json!({ "name": name }))
+            .send()
+    };
+    assert_eq!(update("Lamp").await.unwrap().status(), 200);
+    let (name, data) = events.next().await;
+    assert_eq!((name.as_str(), &data["record"]["name"]), ("products/prod_csv", &json!("Lamp")));
+
+    let response = client
+        .delete(format!("{url}/api/stores/store_csv/api-keys/{}", key["id"].as_str().unwrap()))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(update("Desk lamp").await.unwrap().status(), 200);
+    events.closed().await;
+}
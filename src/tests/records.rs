@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+
+use super::TestApp;
+use crate::models::pocketbase::{generate_id, ID_LENGTH};
+
+async fn register(url: &str, id: Option<&str>, username: &str) -> (u16, Value) {
+    let mut user = json!({
+        "email": format!("{username}@example.com"),
+        "username": username,
+        "password": "correct horse battery staple",
+        "passwordConfirm": "correct horse battery staple",
+    });
+    if let Some(id) = id {
+        user["id"] = json!(id);
+    }
+
+    let response = reqwest::Client::new()
+        .post(format!("{url}/api/collections/users/records"))
+        .json(&user)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[test]
+fn generated_ids_are_random() {
+    let ids: Vec<String> = (0..1000).map(|_| generate_id()).collect();
+
+    for id in &ids {
+        assert_eq!(id.len(), ID_LENGTH);
+        assert!(id.bytes().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit()));
+    }
+    let mut unique = ids.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), ids.len());
+}
+
+#[tokio::test]
+async fn client_ids_are_validated() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+
+    let (status, body) = register(&url, None, "generated").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["id"].as_str().unwrap().len(), ID_LENGTH);
+
+    let (status, body) = register(&url, Some("abcdefghij12345"), "chosen").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["id"], "abcdefghij12345");
+
+    let (status, body) = register(&url, Some("abcdefghij12345"), "taken").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["id"]["code"], "validation_not_unique");
+
+    let (status, body) = register(&url, Some("short"), "short").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["id"]["code"], "validation_length_out_of_range");
+
+    let (status, body) = register(&url, Some("ABCDEFGHIJ12345"), "upper").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["data"]["id"]["code"], "validation_invalid_format");
+}
+
+async fn filter_categories(url: &str, filter: &str) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(format!("{url}/api/collections/categories/records"))
+        .query(&[("filter", filter), ("sort", "created")])
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn datetime_filters_compare_both_formats() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/timestamps.sql")
+        .build()
+        .await;
+    let url = app.serve().await;
+
+    for (filter, expected) in [
+        ("created < '2024-09-01 10:00:00.500Z'", vec!["legacy000000001"]),
+        ("created >= '2024-09-01 10:00:00'", vec!["legacy000000001", "current00000001", "later0000000001"]),
+        ("created > '2024-09-01T10:00:00Z' && created < '2024-09-02'", vec!["current00000001"]),
+        ("created >= '2024-09-02T10:00:00+02:00' || slug = 'legacy'", vec!["legacy000000001", "later0000000001"]),
+        ("created > @now", vec![]),
+    ] {
+        let (status, body) = filter_categories(&url, filter).await;
+        assert_eq!(status, 200, "{filter}");
+        let ids: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, expected, "{filter}");
+    }
+
+    for filter in ["created > 'yesterday'", "unknown = 1", "name = ", "(slug = 'legacy'"] {
+        let (status, body) = filter_categories(&url, filter).await;
+        assert_eq!(status, 400, "{filter}");
+        assert_eq!(body["message"], "Invalid filter parameters.");
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use super::TestApp;
+use crate::models::fixture::{self, Dataset, OnConflict};
+
+#[tokio::test]
+async fn sample_data_is_valid() {
+    let app = TestApp::builder().fixture("data/sample.json").build().await;
+
+    let products: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM products")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(products > 0);
+}
+
+#[tokio::test]
+async fn csv_cells_follow_column_types() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/catalog")
+        .build()
+        .await;
+
+    let (inventory, rating, active, description): (i64, f64, bool, Option<String>) = sqlx::query_as(
+        "SELECT p.inventory, p.rating, p.active, c.description FROM products p JOIN categories c ON c.id = p.category WHERE p.id = 'prod_csv'",
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!((inventory, rating, active, description), (12, 4.5, false, None));
+
+    let password_hash: Option<String> = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = 'user_csv'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(password_hash.is_some_and(|hash| hash.starts_with("$argon2id$")));
+}
+
+#[tokio::test]
+async fn invalid_rows_are_reported() {
+    let app = TestApp::builder().build().await;
+    let mut dataset = Dataset::default();
+    dataset
+        .add(&app.pool, &PathBuf::from("src/tests/fixtures/invalid/categories.csv"))
+        .await
+        .unwrap();
+
+    let report = fixture::import(&app.pool, &dataset, OnConflict::Update).await.unwrap();
+    assert_eq!(report.imported, [("categories", 1)]);
+    let failed: Vec<(usize, &str)> = report
+        .errors
+        .iter()
+        .map(|error| (error.row, error.id.as_deref().unwrap()))
+        .collect();
+    assert_eq!(failed, [(2, "cat_unnamed"), (3, "cat_duplicate")]);
+    assert!(report.errors[1].message.contains("slug: Value is already in use."));
+}
+
+#[tokio::test]
+async fn existing_records_are_updated_or_skipped() {
+    let app = TestApp::builder()
+        .fixture("src/tests/fixtures/categories.sql")
+        .build()
+        .await;
+    let mut dataset = Dataset::default();
+    dataset
+        .add(&app.pool, &PathBuf::from("src/tests/fixtures/renamed.json"))
+        .await
+        .unwrap();
+    let name = || async {
+        sqlx::query_scalar::<_, String>("SELECT name FROM categories WHERE id = 'cat_fixture'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap()
+    };
+
+    let report = fixture::import(&app.pool, &dataset, OnConflict::Skip).await.unwrap();
+    assert_eq!(report.skipped, 1);
+    assert_eq!(name().await, "Fixture");
+
+    fixture::import(&app.pool, &dataset, OnConflict::Update).await.unwrap();
+    assert_eq!(name().await, "Renamed");
+}
@@ -0,0 +1,49 @@
+use serde_json::{json, Value};
+
+use super::{sign_up, TestApp};
+
+async fn post(url: &str, path: &str, token: Option<&str>, body: Value) -> (u16, Value) {
+    let mut request = reqwest::Client::new().post(format!("{url}{path}")).json(&body);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap_or(Value::Null))
+}
+
+async fn email_and_verified(app: &TestApp, id: &str) -> (String, bool) {
+    sqlx::query_as("SELECT email, verified FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn users_cannot_verify_themselves_or_change_their_email() {
+    let app = TestApp::builder().build().await;
+    let url = app.serve().await;
+    let (id, token) = sign_up(&url, "mallory").await;
+
+    let response = reqwest::Client::new()
+        .patch(format!("{url}/api/collections/users/records/{id}"))
+        .bearer_auth(&token)
+        .json(&json!({ "verified": true, "email": "ceo@example.com", "name": "Mallory" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["name"], "Mallory");
+    assert_eq!(body["verified"], false);
+
+    let requests = json!([
+        { "method": "PATCH", "url": format!("/api/collections/users/records/{id}"),
+          "body": { "verified": true, "email": "ceo@example.com" } },
+    ]);
+    let (status, body) = post(&url, "/api/batch", Some(&token), json!({ "requests": requests })).await;
+    assert_eq!(status, 200, "{body}");
+
+    assert_eq!(email_and_verified(&app, &id).await, ("mallory@example.com".to_string(), false));
+}
@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+use axum::extract::{FromRef, FromRequestParts, Request, State};
+use axum::http::{header, request::Parts};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::errors::{AuthenticateError, Error};
+use crate::models::api_key::ApiKey;
+use crate::models::audit::{self, AuditLog};
+use crate::models::auth::{AuthModel, API_KEYS_COLLECTION, SUPERUSERS_COLLECTION, USERS_COLLECTION};
+
+/// Header accepted as an alternative to `Authorization: Bearer <api key>`.
+const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: String,
+    /// Superuser acting as this user through an impersonation token.
+    pub impersonator: Option<String>,
+    /// Unix time the token expires at.
+    pub expires_at: i64,
+}
+
+/// The credentials a request was made with: a user or superuser access token
+/// or a store API key.
+#[derive(Debug, Clone)]
+pub enum Auth {
+    User(AuthUser),
+    Superuser(AuthUser),
+    ApiKey(Box<ApiKey>),
+}
+
+impl Auth {
+    pub fn user(&self) -> Option<&AuthUser> {
+        match self {
+            Auth::User(user) => Some(user),
+            Auth::Superuser(_) | Auth::ApiKey(_) => None,
+        }
+    }
+
+    pub fn superuser(&self) -> Option<&AuthUser> {
+        match self {
+            Auth::Superuser(superuser) => Some(superuser),
+            Auth::User(_) | Auth::ApiKey(_) => None,
+        }
+    }
+
+    pub fn api_key(&self) -> Option<&ApiKey> {
+        match self {
+            Auth::ApiKey(api_key) => Some(api_key),
+            Auth::User(_) | Auth::Superuser(_) => None,
+        }
+    }
+
+    pub fn is_superuser(&self) -> bool {
+        matches!(self, Auth::Superuser(_))
+    }
+
+    /// Unix time the access token expires at. API keys don't expire.
+    pub fn expires_at(&self) -> Option<i64> {
+        match self {
+            Auth::User(user) | Auth::Superuser(user) => Some(user.expires_at),
+            Auth::ApiKey(_) => None,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Auth
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_header = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        let auth_model = AuthModel::new();
+
+        let token = match auth_model.extract_token(auth_header) {
+            Some(token) => token,
+            None => parts
+                .headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+                .ok_or(AuthenticateError::InvalidToken)?,
+        };
+
+        if ApiKey::is_api_key(&token) {
+            let pool = SqlitePool::from_ref(state);
+            return ApiKey::authenticate(&pool, &token)
+                .await?
+                .map(|api_key| Auth::ApiKey(Box::new(api_key)))
+                .ok_or_else(|| AuthenticateError::InvalidToken.into());
+        }
+
+        verify_access_token(&token)
+    }
+}
+
+/// Authenticates a user or superuser access token.
+pub fn verify_access_token(token: &str) -> Result<Auth, Error> {
+    let claims = AuthModel::new()
+        .verify_token(token)
+        .map_err(|_| AuthenticateError::InvalidToken)?;
+    if !claims.authorized || claims.access_uuid.is_none() {
+        return Err(AuthenticateError::InvalidToken.into());
+    }
+
+    let superuser = claims.is_superuser();
+    let user = AuthUser {
+        id: claims.user_id,
+        impersonator: claims.impersonator,
+        expires_at: claims.exp,
+    };
+
+    Ok(if superuser {
+        Auth::Superuser(user)
+    } else {
+        Auth::User(user)
+    })
+}
+
+/// Mints a file token carrying the credentials of `auth`.
+pub fn create_file_token(auth: &Auth) -> Result<String, Error> {
+    let (id, collection, impersonator) = match auth {
+        Auth::User(user) => (user.id.as_str(), USERS_COLLECTION, user.impersonator.clone()),
+        Auth::Superuser(superuser) => (superuser.id.as_str(), SUPERUSERS_COLLECTION, None),
+        Auth::ApiKey(api_key) => (api_key.id.as_str(), API_KEYS_COLLECTION, None),
+    };
+
+    AuthModel::new()
+        .create_file_token(id, collection, impersonator)
+        .map_err(|_| AuthenticateError::TokenCreation.into())
+}
+
+/// Authenticates a file token. The API key it was minted for must still be
+/// active.
+pub async fn verify_file_token(pool: &SqlitePool, token: &str) -> Result<Auth, Error> {
+    let claims = AuthModel::new()
+        .verify_token(token)
+        .map_err(|_| AuthenticateError::InvalidToken)?;
+    if !claims.file {
+        return Err(AuthenticateError::InvalidToken.into());
+    }
+
+    if claims.collection == API_KEYS_COLLECTION {
+        return ApiKey::find_active(pool, &claims.user_id)
+            .await?
+            .map(|api_key| Auth::ApiKey(Box::new(api_key)))
+            .ok_or_else(|| AuthenticateError::InvalidToken.into());
+    }
+
+    let superuser = claims.is_superuser();
+    let user = AuthUser {
+        id: claims.user_id,
+        impersonator: claims.impersonator,
+        expires_at: claims.exp,
+    };
+
+    Ok(if superuser {
+        Auth::Superuser(user)
+    } else {
+        Auth::User(user)
+    })
+}
+
+/// Like `Auth` but lets anonymous requests through. Requests carrying invalid
+/// credentials are still rejected instead of being treated as anonymous.
+#[derive(Debug, Clone)]
+pub struct OptionalAuth(pub Option<Auth>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OptionalAuth
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let has_credentials = parts.headers.contains_key(header::AUTHORIZATION)
+            || parts.headers.contains_key(API_KEY_HEADER);
+        if !has_credentials {
+            return Ok(Self(None));
+        }
+
+        Auth::from_request_parts(parts, state).await.map(|auth| Self(Some(auth)))
+    }
+}
+
+/// Tags requests made with an impersonation token in the logs and records the
+/// ones that modify data in the audit log.
+pub async fn trace_impersonation(State(pool): State<SqlitePool>, request: Request, next: Next) -> Response {
+    let auth_model = AuthModel::new();
+    let claims = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| auth_model.extract_token(Some(value)))
+        .and_then(|token| auth_model.verify_token(&token).ok());
+    let Some((impersonator, user_id)) =
+        claims.and_then(|claims| Some((claims.impersonator?, claims.user_id)))
+    else {
+        return next.run(request).await;
+    };
+
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let span = info_span!("impersonation", %impersonator, user = %user_id);
+    info!(parent: &span, %method, %uri, "Impersonated request");
+
+    let response = next.run(request).instrument(span).await;
+
+    if !method.is_safe() {
+        let details = json!({
+            "method": method.as_str(),
+            "uri": uri.to_string(),
+            "status": response.status().as_u16(),
+        });
+        let entry = AuditLog::new(&impersonator, audit::IMPERSONATED_REQUEST, Some(&user_id), details);
+        if let Err(err) = entry.insert(&pool).await {
+            warn!("Failed to write audit log: {}", err);
+        }
+    }
+
+    response
+}